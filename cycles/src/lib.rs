@@ -0,0 +1,105 @@
+// cycle detection for anything we simulate by applying the same step over and over.
+// the step function makes the next state from the current one, and the fingerprint function
+// boils a state down to something cheap to compare (it can just clone the state, if that's cheap enough).
+// we use Brent's algorithm, so there's only ever a couple of states alive at once.
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Cycle {
+    // how many steps before we first reach a state that's part of the cycle (mu)
+    start: u64,
+    // how many steps it takes to get back round to the same state (lambda)
+    length: u64,
+}
+
+impl Cycle {
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    // the earliest iteration that ends up in the same state as this one
+    pub fn equivalent_iteration(&self, iteration: u64) -> u64 {
+        if iteration < self.start {
+            iteration
+        } else {
+            self.start + (iteration - self.start) % self.length
+        }
+    }
+}
+
+// never returns if the states never repeat - so only use this on things with finitely many states
+pub fn find_cycle<S, K>(initial: &S, step: impl Fn(&S) -> S, fingerprint: impl Fn(&S) -> K) -> Cycle
+    where S: Clone, K: Eq {
+    // first find the cycle length: the tortoise jumps ahead to the hare every power of two steps,
+    // and the hare keeps going until it catches up with the tortoise again
+    let (mut power, mut length) = (1, 1);
+    let mut tortoise = fingerprint(initial);
+    let mut hare = step(initial);
+    let mut hare_fingerprint = fingerprint(&hare);
+    while tortoise != hare_fingerprint {
+        if power == length {
+            tortoise = hare_fingerprint;
+            power *= 2;
+            length = 0;
+        }
+
+        hare = step(&hare);
+        hare_fingerprint = fingerprint(&hare);
+        length += 1;
+    }
+
+    // then start again with the hare one cycle length ahead - they meet at the start of the cycle
+    let mut tortoise = initial.clone();
+    let mut hare = initial.clone();
+    for _ in 0 .. length {
+        hare = step(&hare);
+    }
+
+    let mut start = 0;
+    while fingerprint(&tortoise) != fingerprint(&hare) {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    Cycle { start, length }
+}
+
+// the state after this many steps, without having to take them all
+pub fn state_at<S, K>(initial: &S, step: impl Fn(&S) -> S, fingerprint: impl Fn(&S) -> K, iteration: u64) -> S
+    where S: Clone, K: Eq {
+    let cycle = find_cycle(initial, &step, fingerprint);
+    let mut state = initial.clone();
+    for _ in 0 .. cycle.equivalent_iteration(iteration) {
+        state = step(&state);
+    }
+
+    state
+}
+
+// whatever we want to know about the state after this many steps
+pub fn metric_at<S, K, M>(initial: &S, step: impl Fn(&S) -> S, fingerprint: impl Fn(&S) -> K,
+    metric: impl Fn(&S) -> M, iteration: u64) -> M
+    where S: Clone, K: Eq {
+    metric(&state_at(initial, step, fingerprint, iteration))
+}
+
+#[test]
+pub fn test_cycle() {
+    // 3 -> 9 -> 27 -> 81 -> 43 -> 29 -> 87 -> 61 -> 83 -> 49 -> 47 -> 41 -> 23 -> 69 -> 7 -> 21 -> 63 -> 89 -> 67 -> 1 -> 3, all mod 100
+    let cycle = find_cycle(&3_u64, |n| n * 3 % 100, |n| *n);
+    assert_eq!(cycle, Cycle { start: 0, length: 20 });
+
+    // a tail of 303 -> 203 -> 103 before joining the cycle above at 3
+    let step = |n: &u64| if *n >= 100 { n - 100 } else { n * 3 % 100 };
+    let cycle = find_cycle(&303, step, |n| *n);
+    assert_eq!((cycle.start(), cycle.length()), (3, 20));
+    assert_eq!(cycle.equivalent_iteration(2), 2);
+    assert_eq!(cycle.equivalent_iteration(23), 3);
+    assert_eq!(cycle.equivalent_iteration(1_000_000_000_000_000_000), 20);
+    assert_eq!(state_at(&303, step, |n| *n, 1_000_000_000_000_000_001), 67);
+    assert_eq!(metric_at(&303, step, |n| *n, |n| n % 10, 5), 7);
+}
@@ -1,6 +1,6 @@
 use num::integer::lcm;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
@@ -39,61 +39,168 @@ pub fn parse_input<'a>(input: &'a str) -> Input<'a> {
     Input { directions, lookup }
 }
 
-pub fn part_1(input: &Input) -> usize {
-    let mut location = "AAA";
-    for (step, direction) in input.directions.iter().cycle().enumerate() {
-        location = input.lookup.get(&(location, *direction)).unwrap();
-        if location == "ZZZ" {
-            return step + 1;
+// the network with nodes replaced by dense indices, plus enough precomputed tables
+// to jump a walker forward any number of steps without walking it there
+#[derive(Debug)]
+pub struct Network<'a> {
+    names: Vec<&'a str>,
+    indices: HashMap<&'a str, usize>,
+    // prefixes[i][node] is where a walker starting at node is after the first i instructions
+    prefixes: Vec<Vec<usize>>,
+    // jumps[k][node] is where a walker starting at node is after 2^k full passes
+    jumps: Vec<Vec<usize>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum NetworkError<'a> {
+    // nothing to follow, so there's no such thing as a pass
+    NoDirections,
+    UnknownNode(&'a str),
+}
+
+impl<'a> Input<'a> {
+    pub fn compile(&self) -> Result<Network<'a>, NetworkError<'a>> {
+        if self.directions.is_empty() {
+            return Err(NetworkError::NoDirections);
+        }
+
+        let names = self.lookup.keys()
+            .map(|(name, _)| *name)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let indices = names.iter()
+            .enumerate()
+            .map(|(index, name)| (*name, index))
+            .collect::<HashMap<_, _>>();
+        let follow = |direction| names.iter()
+            .map(|name| {
+                let target = self.lookup[&(*name, direction)];
+                indices.get(target).copied().ok_or(NetworkError::UnknownNode(target))
+            })
+            .collect::<Result<Vec<_>, _>>();
+        let left = follow(Direction::Left)?;
+        let right = follow(Direction::Right)?;
+
+        let mut prefixes = vec![(0..names.len()).collect::<Vec<_>>()];
+        for direction in &self.directions {
+            let step = match direction {
+                Direction::Left => &left,
+                Direction::Right => &right,
+            };
+            let next = prefixes.last().unwrap().iter().map(|node| step[*node]).collect();
+            prefixes.push(next);
+        }
+
+        // 64 levels covers any number of passes that fits in a u64
+        let mut jumps = vec![prefixes.last().unwrap().clone()];
+        for _ in 1..64 {
+            let last = jumps.last().unwrap();
+            let next = last.iter().map(|node| last[*node]).collect();
+            jumps.push(next);
+        }
+
+        Ok(Network { names, indices, prefixes, jumps })
+    }
+}
+
+impl<'a> Network<'a> {
+    pub fn node(&self, name: &str) -> Option<usize> {
+        self.indices.get(name).copied()
+    }
+
+    pub fn name(&self, node: usize) -> &'a str {
+        self.names[node]
+    }
+
+    pub fn nodes_matching(&self, predicate: impl Fn(&str) -> bool) -> Vec<usize> {
+        (0..self.names.len()).filter(|node| predicate(self.names[*node])).collect()
+    }
+
+    pub fn pass_length(&self) -> usize {
+        self.prefixes.len() - 1
+    }
+
+    pub fn position_after(&self, start: usize, steps: u64) -> usize {
+        let pass_length = self.pass_length() as u64;
+        let (mut passes, remainder) = (steps / pass_length, steps % pass_length);
+        let mut node = start;
+        let mut level = 0;
+        while passes > 0 {
+            if passes & 1 == 1 {
+                node = self.jumps[level][node];
+            }
+            passes >>= 1;
+            level += 1;
+        }
+
+        self.prefixes[remainder as usize][node]
+    }
+
+    // number of steps before a walker from start first lands on an end node, if it ever does
+    pub fn steps_until(&self, start: usize, is_end: impl Fn(&str) -> bool) -> Option<u64> {
+        let ends = self.names.iter().map(|name| is_end(name)).collect::<Vec<_>>();
+        // each pass starts at instruction 0, so after one pass per node we must be going round in circles
+        let mut node = start;
+        for pass in 0..self.names.len() as u64 {
+            if let Some(offset) = (1..self.prefixes.len()).find(|i| ends[self.prefixes[*i][node]]) {
+                return Some(pass * self.pass_length() as u64 + offset as u64);
+            }
+            node = self.jumps[0][node];
         }
+
+        None
     }
+}
 
-    unreachable!()
+pub fn part_1(input: &Input) -> usize {
+    part_1_with(input, "AAA", |node| node == "ZZZ").unwrap()
 }
 
-#[derive(Debug)]
-pub struct State {
-    potential_endpoints: Vec<usize>,
-    offset: usize,
-    cycle_length: usize,
+// None if the network doesn't compile, there's no such start node, or an end is never reached
+pub fn part_1_with(input: &Input, start: &str, is_end: impl Fn(&str) -> bool) -> Option<usize> {
+    let network = input.compile().ok()?;
+    let start = network.node(start)?;
+    network.steps_until(start, is_end).map(|steps| steps as usize)
 }
 
 pub fn part_2(input: &Input) -> usize {
-    let start_points = input.lookup.keys().filter(|(k, _)| k.ends_with('A')).map(|(k, _)| *k).collect::<HashSet<_>>();
-    let mut start_states = HashMap::new();
-    for start in start_points {
-        let mut state = HashMap::new();
-        let mut location = start;
-        for (step, direction) in input.directions.iter().cycle().enumerate() {
-            location = input.lookup.get(&(location, *direction)).unwrap();
-            if location.ends_with('Z') {
-                // found an end point - have we seen it before?
-                // cycle detection actually incorrect here (in general case) - we also need to make sure
-                // we're at the same offset into the instruction sequence too!
-                if let Some(offset) = state.get(location) {
-                    start_states.insert(start, State {
-                        potential_endpoints: state.values().copied().collect(),
-                        offset: *offset,
-                        cycle_length: step + 1 - offset,
-                    });
-                    break;
-                } else {
-                    state.insert(location, step + 1);
-                }
+    part_2_with(input, |node| node.ends_with('A'), |node| node.ends_with('Z'))
+}
+
+pub fn part_2_with<'a>(input: &Input<'a>, is_start: impl Fn(&str) -> bool, is_end: impl Fn(&str) -> bool) -> usize {
+    let start_points = input.lookup.keys().filter(|(k, _)| is_start(k)).map(|(k, _)| *k).collect::<HashSet<_>>();
+    // walk from one end point to the next, keeping track of how many steps we've taken in total
+    let next_end = |&(location, steps): &(&'a str, usize)| -> (&'a str, usize) {
+        let (mut location, mut steps) = (location, steps);
+        loop {
+            location = input.lookup[&(location, input.directions[steps % input.directions.len()])];
+            steps += 1;
+            if is_end(location) {
+                return (location, steps);
             }
         }
+    };
+
+    let mut cycle_lengths = Vec::new();
+    for start in start_points {
+        // we've only really been here before if we're at the same offset into the instructions too
+        let cycle = cycles::find_cycle(&(start, 0), next_end, |(location, steps)| (*location, steps % input.directions.len()));
+        let mut state = (start, 0);
+        for _ in 0 .. cycle.start() {
+            state = next_end(&state);
+        }
+        let cycle_start = state.1;
+        for _ in 0 .. cycle.length() {
+            state = next_end(&state);
+        }
+        cycle_lengths.push(state.1 - cycle_start);
     }
 
-    dbg!(&start_states);
     // turns out each start only reaches a single end and has offset == cycle length
     // so no need to be clever
     // might write up the clever version later?
-    let mut result = 1_usize;
-    for state in start_states {
-        result = lcm(result, state.1.cycle_length);
-    }
-
-    result
+    cycle_lengths.into_iter().fold(1, lcm)
 }
 
 fn main() {
@@ -147,3 +254,32 @@ XXX = (XXX, XXX)";
     let input = dbg!(parse_input(input));
     assert_eq!(part_2(&input), 6);
 }
+
+#[test]
+pub fn test_jump_tables() {
+    let input = r"LLR
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)";
+
+    let input = parse_input(input);
+    let network = input.compile().unwrap();
+    let start = network.node("AAA").unwrap();
+    let mut location = "AAA";
+    for (step, direction) in input.directions.iter().cycle().take(20).enumerate() {
+        assert_eq!(network.name(network.position_after(start, step as u64)), location);
+        location = input.lookup[&(location, *direction)];
+    }
+
+    assert_eq!(network.name(network.position_after(start, 1_000_000_000_000_000_000)), "ZZZ");
+    assert_eq!(part_1_with(&input, "AAA", |node| node == "BBB"), Some(1));
+    assert_eq!(part_1_with(&input, "CCC", |node| node == "ZZZ"), None);
+    assert_eq!(part_1_with(&input, "ZZZ", |node| node == "AAA"), None);
+
+    let input = parse_input("\n\nAAA = (AAA, AAA)");
+    assert_eq!(input.compile().unwrap_err(), NetworkError::NoDirections);
+    let input = parse_input("L\n\nAAA = (BBB, AAA)");
+    assert_eq!(input.compile().unwrap_err(), NetworkError::UnknownNode("BBB"));
+    assert_eq!(network.steps_until(network.node("ZZZ").unwrap(), |node| node == "AAA"), None);
+}
@@ -1,23 +1,100 @@
 use itertools::Itertools;
+use num::{BigInt, BigRational, One, ToPrimitive, Zero};
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub enum Direction { Forwards, Backwards }
 
-pub fn predict_additional_number(numbers: &[i64], direction: Direction) -> i64 {
-    // base case - always 0 either way we go
-    if numbers.iter().all(|n| n == &0) {
-        return 0;
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum ModelError {
+    // the differences never reached all zeroes before we ran out of numbers
+    NotPolynomial,
+    // the prediction is exact, but doesn't fit in an i64
+    Overflow,
+}
+
+// a polynomial fitted exactly to a sequence, treating the first number as index 0
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SequenceModel {
+    // the leading entry of each difference sequence, i.e. the coefficients in the Newton
+    // forward-difference basis: f(x) = sum of differences[k] * (x choose k)
+    differences: Vec<BigInt>,
+}
+
+impl SequenceModel {
+    pub fn fit(numbers: &[i64]) -> Result<Self, ModelError> {
+        let mut differences = Vec::new();
+        let mut sequence = numbers.iter().map(|n| BigInt::from(*n)).collect::<Vec<_>>();
+        while !sequence.iter().all(Zero::is_zero) {
+            differences.push(sequence[0].clone());
+            sequence = sequence.iter()
+                .tuple_windows()
+                .map(|(first, second)| second - first)
+                .collect();
+        }
+
+        // if we only got to zero because we ran out of numbers, we can't trust the fit
+        if sequence.is_empty() {
+            return Err(ModelError::NotPolynomial);
+        }
+
+        Ok(Self { differences })
+    }
+
+    // the all-zero sequence is treated as degree 0
+    pub fn degree(&self) -> usize {
+        self.differences.len().saturating_sub(1)
     }
 
-    let difference_sequence = numbers.iter()
-        .tuple_windows()
-        .map(|(first, second)| second - first)
-        .collect::<Vec<_>>();
-    let additional_number = predict_additional_number(&difference_sequence, direction);
-    match direction {
-        Direction::Forwards => *numbers.last().unwrap() + additional_number,
-        Direction::Backwards => *numbers.first().unwrap() - additional_number,
+    pub fn newton_coefficients(&self) -> &[BigInt] {
+        &self.differences
     }
+
+    // coefficients of 1, x, x^2, ... - expanded out of the Newton basis
+    pub fn coefficients(&self) -> Vec<BigRational> {
+        let mut coefficients = vec![BigRational::zero(); self.differences.len().max(1)];
+        // falling factorial x(x-1)...(x-k+1) as coefficients of 1, x, x^2, ...
+        let mut falling = vec![BigInt::one()];
+        let mut factorial = BigInt::one();
+        for (k, difference) in self.differences.iter().enumerate() {
+            for (power, coefficient) in falling.iter().enumerate() {
+                coefficients[power] += BigRational::new(difference * coefficient, factorial.clone());
+            }
+
+            // multiply through by (x - k) ready for the next term
+            let k = BigInt::from(k);
+            let mut next = vec![BigInt::zero(); falling.len() + 1];
+            for (power, coefficient) in falling.iter().enumerate() {
+                next[power + 1] += coefficient;
+                next[power] -= coefficient * &k;
+            }
+            falling = next;
+            factorial *= k + 1;
+        }
+
+        coefficients
+    }
+
+    pub fn evaluate(&self, index: i64) -> BigInt {
+        let index = BigInt::from(index);
+        let mut result = BigInt::zero();
+        // running value of (index choose k) - always an integer, even for negative indices
+        let mut binomial = BigInt::one();
+        for (k, difference) in self.differences.iter().enumerate() {
+            result += difference * &binomial;
+            binomial = binomial * (&index - k) / (k + 1);
+        }
+
+        result
+    }
+}
+
+pub fn predict_additional_number(numbers: &[i64], direction: Direction) -> Result<i64, ModelError> {
+    let model = SequenceModel::fit(numbers)?;
+    let index = match direction {
+        Direction::Forwards => numbers.len() as i64,
+        Direction::Backwards => -1,
+    };
+    model.evaluate(index).to_i64().ok_or(ModelError::Overflow)
 }
 
 pub fn parse_input(input: &str) -> Vec<Vec<i64>> {
@@ -28,17 +105,17 @@ pub fn parse_input(input: &str) -> Vec<Vec<i64>> {
         .collect()
 }
 
-pub fn solve(sequences: &[Vec<i64>], direction: Direction) -> i64 {
+pub fn solve(sequences: &[Vec<i64>], direction: Direction) -> Result<i64, ModelError> {
     sequences.iter()
-        .map(|seq| predict_additional_number(&seq, direction))
+        .map(|seq| predict_additional_number(seq, direction))
         .sum()
 }
 
 fn main() {
     let input = include_str!("../input.txt");
     let sequences = parse_input(input);
-    println!("Part 1: {}", solve(&sequences, Direction::Forwards));
-    println!("Part 2: {}", solve(&sequences, Direction::Backwards));
+    println!("Part 1: {:?}", solve(&sequences, Direction::Forwards));
+    println!("Part 2: {:?}", solve(&sequences, Direction::Backwards));
 }
 
 #[test]
@@ -48,6 +125,22 @@ pub fn test() {
 10 13 16 21 30 45";
 
     let sequences = parse_input(input);
-    assert_eq!(solve(&sequences, Direction::Forwards), 114);
-    assert_eq!(solve(&sequences, Direction::Backwards), 2);
-}
\ No newline at end of file
+    assert_eq!(solve(&sequences, Direction::Forwards), Ok(114));
+    assert_eq!(solve(&sequences, Direction::Backwards), Ok(2));
+}
+
+#[test]
+pub fn test_sequence_model() {
+    // 1 + x + x^2 / 2 + x^3 / 2
+    let model = SequenceModel::fit(&[1, 3, 9, 22, 45, 81]).unwrap();
+    assert_eq!(model.degree(), 3);
+    let half = BigRational::new(1.into(), 2.into());
+    assert_eq!(model.coefficients(), vec![BigRational::one(), BigRational::one(), half.clone(), half]);
+    assert_eq!(model.evaluate(-3), BigInt::from(-11));
+    assert_eq!(model.evaluate(2_000_000), "4000002000002000001".parse::<BigInt>().unwrap());
+    assert_eq!(predict_additional_number(&[1, 3, 9, 22, 45, 81], Direction::Forwards), Ok(133));
+
+    assert_eq!(SequenceModel::fit(&[1, 2, 4, 8, 16]), Err(ModelError::NotPolynomial));
+    assert_eq!(predict_additional_number(&[0, i64::MAX], Direction::Forwards), Err(ModelError::NotPolynomial));
+    assert_eq!(predict_additional_number(&[0, i64::MAX / 2, i64::MAX - 1], Direction::Forwards), Err(ModelError::Overflow));
+}
@@ -153,49 +153,217 @@ impl Network {
         distances
     }
 
-    pub fn only_loop(&self) -> Network {
-        let distances = self.find_loop_distances();
-        let mut pipes_in_loop = self.pipe_locations.iter()
-            .map(|(c, p)| (*c, *p))
-            .filter(|(c, _)| distances.contains_key(&c))
+    pub fn only_loop(&self) -> Result<Network, StartShapeError> {
+        let start_shape = self.infer_start_shape()?;
+        let mut pipes = self.pipe_locations.clone();
+        pipes.insert(self.start_point, start_shape);
+        let (cells, _) = trace_loop(&pipes, self.start_point);
+        let pipes_in_loop = cells.into_iter()
+            .map(|c| (c, pipes[&c]))
             .collect::<HashMap<_, _>>();
 
-        // insert the start location also
-        let pipe_from_above = match pipes_in_loop.get(&self.start_point.next_north()) {
-            Some(PipeShape::VerticalPipe) | Some(PipeShape::FPipe) | Some(PipeShape::SevenPipe) => true,
-            _ => false,
-        };
-        let pipe_from_below = match pipes_in_loop.get(&self.start_point.next_south()) {
-            Some(PipeShape::VerticalPipe) | Some(PipeShape::JPipe) | Some(PipeShape::LPipe) => true,
-            _ => false,
-        };
-        let pipe_from_left = match pipes_in_loop.get(&self.start_point.next_west()) {
-            Some(PipeShape::HorizontalPipe) | Some(PipeShape::FPipe) | Some(PipeShape::LPipe) => true,
-            _ => false,
-        };
-        let pipe_from_right = match pipes_in_loop.get(&self.start_point.next_east()) {
-            Some(PipeShape::HorizontalPipe) | Some(PipeShape::JPipe) | Some(PipeShape::SevenPipe) => true,
-            _ => false,
-        };
-
-        match (pipe_from_above, pipe_from_below, pipe_from_left, pipe_from_right) {
-            (true, true, false, false) => pipes_in_loop.insert(self.start_point, PipeShape::VerticalPipe),
-            (true, false, true, false) => pipes_in_loop.insert(self.start_point, PipeShape::JPipe),
-            (true, false, false, true) => pipes_in_loop.insert(self.start_point, PipeShape::LPipe),
-            (false, true, true, false) => pipes_in_loop.insert(self.start_point, PipeShape::SevenPipe),
-            (false, true, false, true) => pipes_in_loop.insert(self.start_point, PipeShape::FPipe),
-            (false, false, true, true) => pipes_in_loop.insert(self.start_point, PipeShape::HorizontalPipe),
-            _ => unreachable!(),
-        };
-
-        Network {
+        Ok(Network {
             start_point: self.start_point,
             max_size: self.max_size,
             pipe_locations: pipes_in_loop
+        })
+    }
+
+    // the start tile is whichever shape closes a loop back through it
+    pub fn infer_start_shape(&self) -> Result<PipeShape, StartShapeError> {
+        let candidates = [
+            PipeShape::VerticalPipe, PipeShape::HorizontalPipe, PipeShape::LPipe,
+            PipeShape::JPipe, PipeShape::FPipe, PipeShape::SevenPipe,
+        ];
+        let mut pipes = self.pipe_locations.clone();
+        let mut shapes = Vec::new();
+        for shape in candidates {
+            pipes.insert(self.start_point, shape);
+            if trace_loop(&pipes, self.start_point).1 {
+                shapes.push(shape);
+            }
+        }
+
+        match shapes.len() {
+            0 => Err(StartShapeError::NoLoop),
+            1 => Ok(shapes[0]),
+            _ => Err(StartShapeError::Ambiguous(shapes)),
+        }
+    }
+
+    // all the pipes, with the start tile filled in if we could work out what it is
+    fn resolved_pipes(&self) -> (HashMap<Coord, PipeShape>, Result<PipeShape, StartShapeError>) {
+        let mut pipes = self.pipe_locations.clone();
+        let start_shape = self.infer_start_shape();
+        if let Ok(shape) = start_shape {
+            pipes.insert(self.start_point, shape);
+        }
+
+        (pipes, start_shape)
+    }
+
+    pub fn analyse(&self) -> Analysis {
+        let (pipes, start_shape) = self.resolved_pipes();
+
+        // do the loop through the start first, so it gets traced from the start tile
+        let mut candidates = pipes.keys().copied().collect::<Vec<_>>();
+        candidates.sort_by_key(|c| (c != &self.start_point, c.y, c.x));
+        let mut visited = HashSet::new();
+        let mut loops = Vec::new();
+        for coord in candidates {
+            if visited.contains(&coord) {
+                continue;
+            }
+
+            // every pipe joins at most two others, so if this trace doesn't close then
+            // nothing it passed through can be on a loop either
+            let (cells, closed) = trace_loop(&pipes, coord);
+            visited.extend(cells.iter().copied());
+            if closed {
+                loops.push(PipeLoop { cells });
+            }
+        }
+
+        let mut loose_ends = Vec::new();
+        for (coord, pipe) in &pipes {
+            for neighbour in coord.neighbours_for(*pipe) {
+                // if we don't know what the start is, assume it would have matched up
+                if start_shape.is_err() && neighbour == self.start_point {
+                    continue;
+                }
+
+                let problem = match pipes.get(&neighbour) {
+                    None => LooseEndKind::Dangling,
+                    Some(other) if !neighbour.neighbours_for(*other).contains(coord) => LooseEndKind::Mismatched,
+                    Some(_) => continue,
+                };
+                loose_ends.push(LooseEnd { coord: *coord, towards: neighbour, kind: problem });
+            }
+        }
+        loose_ends.sort_by_key(|e| (e.coord.y, e.coord.x, e.towards.y, e.towards.x));
+
+        Analysis { start_point: self.start_point, start_shape, loops, loose_ends }
+    }
+}
+
+// follows the pipes from start (leaving by its first connection) until we either get back
+// to start, or reach a pipe that doesn't connect back to where we came from
+fn trace_loop(pipes: &HashMap<Coord, PipeShape>, start: Coord) -> (Vec<Coord>, bool) {
+    let mut cells = vec![start];
+    let mut previous = start;
+    let mut current = start.neighbours_for(pipes[&start])[0];
+    while current != start {
+        let Some(pipe) = pipes.get(&current) else {
+            return (cells, false);
+        };
+        let neighbours = current.neighbours_for(*pipe);
+        if !neighbours.contains(&previous) {
+            return (cells, false);
+        }
+
+        cells.push(current);
+        let next = if neighbours[0] == previous { neighbours[1] } else { neighbours[0] };
+        previous = current;
+        current = next;
+    }
+
+    // we made it round - but the start also has to connect back to the last pipe
+    let closed = start.neighbours_for(pipes[&start]).contains(&previous);
+    (cells, closed)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum StartShapeError {
+    NoLoop,
+    Ambiguous(Vec<PipeShape>),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Orientation {
+    Clockwise,
+    CounterClockwise,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PipeLoop {
+    // in the order we walked them
+    cells: Vec<Coord>,
+}
+
+impl PipeLoop {
+    pub fn cells(&self) -> &[Coord] {
+        &self.cells
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    // furthest point round the loop from where we started tracing it, and how far that is
+    pub fn farthest_point(&self) -> (Coord, usize) {
+        let distance = self.cells.len() / 2;
+        (self.cells[distance], distance)
+    }
+
+    // as drawn on screen, i.e. with y increasing downwards
+    pub fn orientation(&self) -> Orientation {
+        let twice_area: i64 = self.cells.iter()
+            .zip(self.cells.iter().cycle().skip(1))
+            .map(|(a, b)| a.x * b.y - b.x * a.y)
+            .sum();
+        if twice_area > 0 {
+            Orientation::Clockwise
+        } else {
+            Orientation::CounterClockwise
         }
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum LooseEndKind {
+    // pointing at a tile with no pipe in it
+    Dangling,
+    // pointing at a pipe that doesn't point back
+    Mismatched,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct LooseEnd {
+    coord: Coord,
+    towards: Coord,
+    kind: LooseEndKind,
+}
+
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    start_point: Coord,
+    start_shape: Result<PipeShape, StartShapeError>,
+    loops: Vec<PipeLoop>,
+    loose_ends: Vec<LooseEnd>,
+}
+
+impl Analysis {
+    pub fn start_shape(&self) -> &Result<PipeShape, StartShapeError> {
+        &self.start_shape
+    }
+
+    pub fn loops(&self) -> &[PipeLoop] {
+        &self.loops
+    }
+
+    pub fn start_loop(&self) -> Option<&PipeLoop> {
+        self.loops.iter().find(|l| l.cells.first() == Some(&self.start_point))
+    }
+
+    pub fn loose_ends(&self) -> &[LooseEnd] {
+        &self.loose_ends
+    }
+}
+
 impl std::fmt::Display for Network {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Coord { x: max_x, y: max_y} = self.max_size;
@@ -222,14 +390,14 @@ pub fn pipe_winding_number_upper(coord: Coord, ordered_loop: &HashMap<Coord, i64
     let current_number = *ordered_loop.get(&coord).unwrap();
     let number_above = *ordered_loop.get(&coord.next_north()).unwrap();
     if current_number == number_above + 1 {
-        return -1;
+        -1
     } else if current_number == number_above - 1 {
-        return 1;
+        1
     } else if current_number == 0 {
         // wraparound?
-        return -1;
+        -1
     } else {
-        return 1;
+        1
     }
 }
 
@@ -237,68 +405,70 @@ pub fn pipe_winding_number_lower(coord: Coord, ordered_loop: &HashMap<Coord, i64
     let current_number = *ordered_loop.get(&coord).unwrap();
     let number_below = *ordered_loop.get(&coord.next_south()).unwrap();
     if current_number == number_below + 1 {
-        return 1;
+        1
     } else if current_number == number_below - 1 {
-        return -1;
+        -1
     } else if current_number == 0 {
         // wraparound?
-        return 1;
+        1
     } else {
-        return -1;
+        -1
     }
 }
 
-pub fn part_2(network: &Network) -> usize {
-    let ordered_loop = network.find_ordered_loop();
-    let network = network.only_loop();
+pub fn find_inside(network: &Network) -> Result<HashSet<Coord>, StartShapeError> {
+    let loop_only = network.only_loop()?;
+    let ordered_loop = loop_only.find_ordered_loop();
+    let network = loop_only;
 
     let mut coords_inside = HashSet::new();
     // now we have just the loop to worry about, and we have a way of ordering it.
     let Coord { x: max_x, y: max_y} = network.max_size;
     for y in 0..=max_y {
-        let upper_pipes_in_row: Vec<_> = network.pipe_locations
-        .iter().filter_map(|(c, p)| 
-            ((*p == PipeShape::VerticalPipe || *p == PipeShape::JPipe || *p == PipeShape::LPipe)
-                 && c.y == y).then_some(*c)).collect();
-
-        let lower_pipes_in_row: Vec<_> = network.pipe_locations
-        .iter().filter_map(|(c, p)| 
-            ((*p == PipeShape::VerticalPipe || *p == PipeShape::SevenPipe || *p == PipeShape::FPipe)
-                && c.y == y).then_some(*c)).collect();
-
+        // winding numbers of all the pipes we've already passed in this row: the loop is closed,
+        // so these are exactly minus the winding numbers of all the pipes still to our right.
+        // need to track two lines: the 'upper half' line, which intersects |, J, and L
+        // and the 'lower half' line, which intersects |, 7, and F
+        let mut upper_winding_number = 0;
+        let mut lower_winding_number = 0;
         for x in 0..=max_x {
-            let current_coord: Coord = (x as i64, y as i64).into();
-            // println!("Considering coord {:?}", current_coord);
-            if network.pipe_locations.contains_key(&current_coord) {
-                // this is part of the network, don't check it
-                continue;
-            }
-
-            // need to check two lines: the 'upper half' line, which intersects |, J, and L
-            // and the 'lower half' line, which intersects |, 7, and F
-            let upper_winding_number: i64 = upper_pipes_in_row.iter().filter(|c| c.x > x)
-                .map(|p| pipe_winding_number_upper(*p, &ordered_loop)).sum();
-
-            let lower_winding_number: i64 = lower_pipes_in_row.iter().filter(|c| c.x > x)
-                .map(|p| pipe_winding_number_lower(*p, &ordered_loop)).sum();
-
-            if upper_winding_number != 0 || lower_winding_number != 0 {
-                // println!("Coord {:?} is inside", current_coord);
-                coords_inside.insert(current_coord);
+            let current_coord: Coord = (x, y).into();
+            match network.pipe_locations.get(&current_coord) {
+                Some(PipeShape::VerticalPipe) => {
+                    upper_winding_number += pipe_winding_number_upper(current_coord, &ordered_loop);
+                    lower_winding_number += pipe_winding_number_lower(current_coord, &ordered_loop);
+                },
+                Some(PipeShape::JPipe) | Some(PipeShape::LPipe) => {
+                    upper_winding_number += pipe_winding_number_upper(current_coord, &ordered_loop);
+                },
+                Some(PipeShape::SevenPipe) | Some(PipeShape::FPipe) => {
+                    lower_winding_number += pipe_winding_number_lower(current_coord, &ordered_loop);
+                },
+                Some(PipeShape::HorizontalPipe) => { },
+                None => {
+                    if upper_winding_number != 0 || lower_winding_number != 0 {
+                        coords_inside.insert(current_coord);
+                    }
+                },
             }
         }
     }
 
-    coords_inside.len()
+    Ok(coords_inside)
+}
+
+pub fn part_2(network: &Network) -> Result<usize, StartShapeError> {
+    Ok(find_inside(network)?.len())
 }
 
-pub fn part_2_geo(network: &Network) -> usize {
-    let mut ordered_loop: Vec<_> = network.find_ordered_loop().into_iter().collect();
+pub fn part_2_geo(network: &Network) -> Result<usize, StartShapeError> {
     // need this to fill in start
-    let network = network.only_loop();
+    let loop_only = network.only_loop()?;
+    let mut ordered_loop: Vec<_> = loop_only.find_ordered_loop().into_iter().collect();
+    let network = loop_only;
     ordered_loop.sort_by_key(|(_, n)| *n);
     ordered_loop.retain(|(c, _)|
-        matches!(network.pipe_locations.get(c).expect(&format!("Nothing in map for {:?}", c).to_owned()),
+        matches!(network.pipe_locations.get(c).unwrap_or_else(|| panic!("Nothing in map for {:?}", c)),
         PipeShape::FPipe | PipeShape::JPipe | PipeShape::SevenPipe | PipeShape::LPipe));
 
     let segments = ordered_loop.into_iter().map(|(c, _)| (c.x, c.y)).collect::<Vec<_>>();
@@ -321,7 +491,78 @@ pub fn part_2_geo(network: &Network) -> usize {
         }
     }
 
-    coords_inside.len()
+    Ok(coords_inside.len())
+}
+
+#[derive(Clone, Debug)]
+pub struct SqueezeFill {
+    enclosed: HashSet<Coord>,
+    // in half-tile coordinates: tile (x, y) is (2x, 2y), and the odd coordinates are the
+    // gaps between tiles - so this includes every gap the air squeezed through
+    outside: HashSet<Coord>,
+}
+
+impl SqueezeFill {
+    pub fn enclosed(&self) -> &HashSet<Coord> {
+        &self.enclosed
+    }
+
+    pub fn outside(&self) -> &HashSet<Coord> {
+        &self.outside
+    }
+
+    pub fn outside_tiles(&self) -> HashSet<Coord> {
+        self.outside.iter()
+            .filter(|c| c.x % 2 == 0 && c.y % 2 == 0)
+            .map(|c| (c.x / 2, c.y / 2).into())
+            .collect()
+    }
+}
+
+// the way the puzzle actually describes it: blow air in from the edges and see where it can get to
+pub fn squeeze_fill(network: &Network) -> Result<SqueezeFill, StartShapeError> {
+    let network = network.only_loop()?;
+
+    // draw the loop at double resolution, so the gap between two adjacent pipes is a cell of its own
+    let mut walls = HashSet::new();
+    for (coord, pipe) in &network.pipe_locations {
+        let doubled: Coord = (coord.x * 2, coord.y * 2).into();
+        walls.insert(doubled);
+        for neighbour in coord.neighbours_for(*pipe) {
+            walls.insert((doubled.x + neighbour.x - coord.x, doubled.y + neighbour.y - coord.y).into());
+        }
+    }
+
+    // leave a border of air all the way round so the fill can get everywhere outside
+    let Coord { x: max_x, y: max_y } = network.max_size;
+    let (min, max): (Coord, Coord) = ((-1, -1).into(), (max_x * 2 + 1, max_y * 2 + 1).into());
+    let mut outside = HashSet::new();
+    let mut coords_to_visit = VecDeque::from([min]);
+    while let Some(coord) = coords_to_visit.pop_front() {
+        if coord.x < min.x || coord.y < min.y || coord.x > max.x || coord.y > max.y
+            || walls.contains(&coord) || !outside.insert(coord) {
+            continue;
+        }
+
+        coords_to_visit.extend([coord.next_north(), coord.next_south(), coord.next_east(), coord.next_west()]);
+    }
+    outside.retain(|c| c.x >= 0 && c.y >= 0 && c.x < max.x && c.y < max.y);
+
+    let mut enclosed = HashSet::new();
+    for y in 0..=max_y {
+        for x in 0..=max_x {
+            let doubled = (x * 2, y * 2).into();
+            if !walls.contains(&doubled) && !outside.contains(&doubled) {
+                enclosed.insert((x, y).into());
+            }
+        }
+    }
+
+    Ok(SqueezeFill { enclosed, outside })
+}
+
+pub fn part_2_squeeze(network: &Network) -> Result<usize, StartShapeError> {
+    Ok(squeeze_fill(network)?.enclosed.len())
 }
 
 pub fn parse_input(input: &str) -> Network {
@@ -342,7 +583,7 @@ pub fn parse_input(input: &str) -> Network {
                 _ => { },
             }
 
-            max_size = Some(current_coord.into());
+            max_size = Some(current_coord);
         }
     }
 
@@ -363,8 +604,9 @@ fn main() {
     let input = include_str!("../input.txt");
     let network = parse_input(input);
     println!("Part 1: {}", part_1(&network));
-    println!("Part 2: {}", part_2(&network));
-    println!("Part 2 geo: {}", part_2_geo(&network));
+    println!("Part 2: {:?}", part_2(&network));
+    println!("Part 2 geo: {:?}", part_2_geo(&network));
+    println!("Part 2 squeeze: {:?}", part_2_squeeze(&network));
 }
 
 #[test]
@@ -403,7 +645,8 @@ pub fn test_part2() {
 ...........";
 
     let network = parse_input(input);
-    assert_eq!(part_2(&network), 4);
+    assert_eq!(part_2(&network), Ok(4));
+    assert_eq!(find_inside(&network).unwrap(), HashSet::from([(2, 6).into(), (3, 6).into(), (7, 6).into(), (8, 6).into()]));
 }
 
 #[test]
@@ -419,7 +662,7 @@ pub fn test_part2_slim() {
 ..........";
 
     let network = parse_input(input);
-    assert_eq!(part_2(&network), 4);
+    assert_eq!(part_2(&network), Ok(4));
 }
 
 
@@ -437,7 +680,7 @@ L--J.L7...LJS7F-7L7.
 ....L---J.LJ.LJLJ...";
 
     let network = parse_input(input);
-    assert_eq!(part_2(&network), 8);
+    assert_eq!(part_2(&network), Ok(8));
 }
 
 #[test]
@@ -454,7 +697,117 @@ L.L7LFJ|||||FJL7||LJ
 L7JLJL-JLJLJL--JLJ.L";
 
     let network = parse_input(input);
-    assert_eq!(part_2(&network), 10);
-    dbg!(part_2_geo(&network));
+    assert_eq!(part_2(&network), Ok(10));
+    assert_eq!(part_2_geo(&network), Ok(10));
+}
+
+#[test]
+pub fn test_part2_squeeze() {
+    let input = r"..........
+.S------7.
+.|F----7|.
+.||....||.
+.||....||.
+.|L-7F-J|.
+.|..||..|.
+.L--JL--J.
+..........";
+
+    let network = parse_input(input);
+    let fill = squeeze_fill(&network).unwrap();
+    assert_eq!(fill.enclosed(), &HashSet::from([(2, 6).into(), (3, 6).into(), (6, 6).into(), (7, 6).into()]));
+    // the air gets in between the two pairs of vertical pipes down the sides
+    assert!(fill.outside().contains(&(5, 6).into()));
+    assert!(fill.outside_tiles().contains(&(3, 3).into()));
+    assert!(!fill.outside_tiles().contains(&(3, 6).into()));
+
+    for input in [
+        r".F----7F7F7F7F-7....
+.|F--7||||||||FJ....
+.||.FJ||||||||L7....
+FJL7L7LJLJ||LJ.L-7..
+L--J.L7...LJS7F-7L7.
+....F-J..F7FJ|L7L7L7
+....L7.F7||L7|.L7L7|
+.....|FJLJ|FJ|F7|.LJ
+....FJL-7.||.||||...
+....L---J.LJ.LJLJ...",
+        r"FF7FSF7F7F7F7F7F---7
+L|LJ||||||||||||F--J
+FL-7LJLJ||||||LJL-77
+F--JF--7||LJLJ7F7FJ-
+L---JF-JLJ.||-FJLJJ7
+|F|F-JF---7F7-L7L|7|
+|FFJF7L7F-JF7|JL---7
+7-L-JL7||F7|L7F-7F7|
+L.L7LFJ|||||FJL7||LJ
+L7JLJL-JLJLJL--JLJ.L",
+    ] {
+        let network = parse_input(input);
+        let squeezed = part_2_squeeze(&network);
+        assert_eq!(squeezed, part_2(&network));
+        assert_eq!(squeezed, part_2_geo(&network));
+    }
 }
 
+
+#[test]
+pub fn test_analysis() {
+    let input = r"F-7.F7.7
+|.|.||.-
+L-J.|S7.
+..F7L-J|
+..LJ.F-J";
+
+    let network = parse_input(input);
+    let analysis = network.analyse();
+    assert_eq!(analysis.start_shape(), &Ok(PipeShape::LPipe));
+    assert_eq!(analysis.loops().iter().map(PipeLoop::len).collect::<Vec<_>>(), vec![10, 8, 4]);
+
+    let start_loop = analysis.start_loop().unwrap();
+    assert_eq!(start_loop.len(), 10);
+    assert_eq!(start_loop.farthest_point(), ((4, 2).into(), 5));
+    assert_eq!(start_loop.orientation(), Orientation::CounterClockwise);
+    let reversed = PipeLoop { cells: start_loop.cells().iter().rev().copied().collect() };
+    assert_eq!(reversed.orientation(), Orientation::Clockwise);
+
+    assert_eq!(analysis.loose_ends(), &[
+        LooseEnd { coord: (7, 0).into(), towards: (6, 0).into(), kind: LooseEndKind::Dangling },
+        LooseEnd { coord: (7, 0).into(), towards: (7, 1).into(), kind: LooseEndKind::Mismatched },
+        LooseEnd { coord: (7, 1).into(), towards: (6, 1).into(), kind: LooseEndKind::Dangling },
+        LooseEnd { coord: (7, 1).into(), towards: (8, 1).into(), kind: LooseEndKind::Dangling },
+        LooseEnd { coord: (7, 3).into(), towards: (7, 2).into(), kind: LooseEndKind::Dangling },
+        LooseEnd { coord: (5, 4).into(), towards: (5, 5).into(), kind: LooseEndKind::Dangling },
+    ]);
+}
+
+#[test]
+pub fn test_ambiguous_start() {
+    // three pipes point at the start, but only one shape closes the loop
+    let input = r"F-7
+|.|
+S-J
+L..";
+
+    let network = parse_input(input);
+    assert_eq!(network.infer_start_shape(), Ok(PipeShape::LPipe));
+
+    // the dangling pipe to the right of the start points back at it too, but is off the loop
+    let network = parse_input("F--7\n|..|\nS-.|\nL--J");
+    assert_eq!(network.infer_start_shape(), Ok(PipeShape::VerticalPipe));
+    assert_eq!(part_2_squeeze(&network), Ok(4));
+    assert_eq!(part_2(&network), Ok(4));
+    assert_eq!(part_2_geo(&network), Ok(4));
+
+    let input = r"F7.
+LS7
+.LJ";
+    let network = parse_input(input);
+    assert_eq!(network.infer_start_shape(), Err(StartShapeError::Ambiguous(vec![PipeShape::JPipe, PipeShape::FPipe])));
+    assert_eq!(part_2(&network), Err(StartShapeError::Ambiguous(vec![PipeShape::JPipe, PipeShape::FPipe])));
+
+    let network = parse_input("S-7\n|..\n...");
+    assert_eq!(part_2(&network), Err(StartShapeError::NoLoop));
+    assert_eq!(part_2_geo(&network), Err(StartShapeError::NoLoop));
+    assert_eq!(part_2_squeeze(&network), Err(StartShapeError::NoLoop));
+}
@@ -1,4 +1,4 @@
-use std::collections::{HashSet, BTreeSet};
+use std::collections::{HashSet, BTreeSet, BTreeMap, BinaryHeap, HashMap};
 
 use itertools::Itertools;
 
@@ -27,13 +27,194 @@ pub struct Input {
     blank_y: BTreeSet<i64>,
 }
 
+// how wide each column (or row) ends up once the universe has expanded
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AxisExpansion {
+    // every blank line becomes this many lines, and everything else stays as it is
+    Uniform(u64),
+    // each line listed becomes that many lines (blank or not), and everything else stays as it is
+    Weighted(BTreeMap<i64, u64>),
+}
+
+impl AxisExpansion {
+    // how much wider than usual each line is, in line order
+    fn extra_widths(&self, blanks: &BTreeSet<i64>) -> Vec<(i64, i64)> {
+        match self {
+            Self::Uniform(factor) => blanks.iter().map(|line| (*line, *factor as i64 - 1)).collect(),
+            Self::Weighted(weights) => weights.iter().map(|(line, width)| (*line, *width as i64 - 1)).collect(),
+        }
+    }
+
+    // where each of the given lines ends up after expansion
+    fn expand(&self, lines: impl Iterator<Item = i64>, blanks: &BTreeSet<i64>) -> HashMap<i64, i64> {
+        let lines = lines.collect::<BTreeSet<_>>();
+        let extra_widths = self.extra_widths(blanks);
+        let mut extra_widths = extra_widths.iter().peekable();
+        let mut shift = 0;
+        let mut expanded = HashMap::new();
+        for line in lines {
+            while let Some((_, extra)) = extra_widths.next_if(|(l, _)| *l < line) {
+                shift += extra;
+            }
+            expanded.insert(line, line + shift);
+        }
+
+        expanded
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Expansion {
+    horizontal: AxisExpansion,
+    vertical: AxisExpansion,
+}
+
+impl Expansion {
+    pub fn new(horizontal: AxisExpansion, vertical: AxisExpansion) -> Self {
+        Self { horizontal, vertical }
+    }
+
+    pub fn uniform(factor: u64) -> Self {
+        Self::new(AxisExpansion::Uniform(factor), AxisExpansion::Uniform(factor))
+    }
+}
+
 impl Input {
-    pub fn adjusted_distance_between(&self, first: Coord, second: Coord, expansion_factor: usize) -> usize {
-        let naive_distance = first.taxicab_distance_to(second);
-        let blank_x_between = self.blank_x.range(first.x.min(second.x) .. first.x.max(second.x)).count();
-        let blank_y_between = self.blank_y.range(first.y.min(second.y) .. first.y.max(second.y)).count();
-        naive_distance as usize + (blank_x_between * (expansion_factor - 1)) + (blank_y_between * (expansion_factor - 1))
+    // the galaxy positions after expansion, in a consistent (sorted) order
+    pub fn expand(&self, expansion: &Expansion) -> Vec<Coord> {
+        self.expand_galaxies(expansion).into_iter().map(|(_, expanded)| expanded).collect()
+    }
+
+    // each galaxy paired with where it ends up after expansion
+    pub fn expand_galaxies(&self, expansion: &Expansion) -> Vec<(Coord, Coord)> {
+        let expanded_x = expansion.horizontal.expand(self.galaxies.iter().map(|c| c.x), &self.blank_x);
+        let expanded_y = expansion.vertical.expand(self.galaxies.iter().map(|c| c.y), &self.blank_y);
+        self.galaxies.iter()
+            .sorted_by_key(|c| (c.y, c.x))
+            .map(|c| (*c, (expanded_x[&c.x], expanded_y[&c.y]).into()))
+            .collect()
+    }
+}
+
+// answers distance questions about the expanded universe, from the galaxies' original positions
+#[derive(Clone, Debug)]
+pub struct GalaxyIndex {
+    // (original, expanded), ordered by expanded x
+    by_x: Vec<(Coord, Coord)>,
+    // where each original galaxy is in by_x
+    positions: HashMap<Coord, usize>,
+    // rotating 45 degrees turns taxicab balls into squares: (x + y, x - y, index into by_x),
+    // ordered by x + y
+    by_sum: Vec<(i64, i64, usize)>,
+}
+
+impl GalaxyIndex {
+    pub fn new(input: &Input, expansion: &Expansion) -> Self {
+        let mut by_x = input.expand_galaxies(expansion);
+        by_x.sort_by_key(|(original, expanded)| (expanded.x, original.y, original.x));
+        let positions = by_x.iter()
+            .enumerate()
+            .map(|(i, (original, _))| (*original, i))
+            .collect();
+        let by_sum = by_x.iter()
+            .enumerate()
+            .map(|(i, (_, e))| (e.x + e.y, e.x - e.y, i))
+            .sorted()
+            .collect();
+        Self { by_x, positions, by_sum }
+    }
+
+    fn distance(&self, first: usize, second: usize) -> u64 {
+        self.by_x[first].1.taxicab_distance_to(self.by_x[second].1)
+    }
+
+    // ties are broken by original position, top to bottom then left to right
+    fn result(&self, index: usize, distance: u64) -> (u64, i64, i64) {
+        let original = self.by_x[index].0;
+        (distance, original.y, original.x)
+    }
+
+    // the k galaxies closest to this one (not including itself), closest first
+    pub fn nearest(&self, galaxy: Coord, k: usize) -> Option<Vec<(Coord, u64)>> {
+        let start = *self.positions.get(&galaxy)?;
+        let start_x = self.by_x[start].1.x;
+        let mut best = BinaryHeap::new();
+        let (mut left, mut right) = (start.checked_sub(1), start + 1);
+        loop {
+            // walk outwards in x - once the x gap alone is worse than the k-th best we're done
+            let left_gap = left.map(|l| start_x - self.by_x[l].1.x);
+            let right_gap = (right < self.by_x.len()).then(|| self.by_x[right].1.x - start_x);
+            let (index, gap) = match (left_gap, right_gap) {
+                (Some(l), Some(r)) if l <= r => (left.unwrap(), l),
+                (Some(l), None) => (left.unwrap(), l),
+                (_, Some(r)) => (right, r),
+                (None, None) => break,
+            };
+            if best.len() == k && best.peek().is_none_or(|(d, _, _)| gap as u64 > *d) {
+                break;
+            }
+
+            if index == right {
+                right += 1;
+            } else {
+                left = index.checked_sub(1);
+            }
+
+            best.push(self.result(index, self.distance(start, index)));
+            if best.len() > k {
+                best.pop();
+            }
+        }
+
+        Some(best.into_sorted_vec().into_iter().map(|(d, y, x)| ((x, y).into(), d)).collect())
+    }
+
+    // every galaxy within this distance of this one (not including itself), closest first
+    pub fn within(&self, galaxy: Coord, distance: u64) -> Option<Vec<(Coord, u64)>> {
+        let start = *self.positions.get(&galaxy)?;
+        let expanded = self.by_x[start].1;
+        let (sum, difference) = (expanded.x + expanded.y, expanded.x - expanded.y);
+        let distance = distance as i64;
+        let from = self.by_sum.partition_point(|(s, _, _)| *s < sum - distance);
+        let to = self.by_sum.partition_point(|(s, _, _)| *s <= sum + distance);
+        let found = self.by_sum[from..to].iter()
+            .filter(|(_, d, i)| *i != start && d.abs_diff(difference) <= distance as u64)
+            .map(|(_, _, i)| self.result(*i, self.distance(start, *i)))
+            .sorted()
+            .map(|(d, y, x)| ((x, y).into(), d))
+            .collect();
+        Some(found)
+    }
+
+    // the furthest distance is always along one of the diagonals, which are sorted for us already
+    pub fn farthest_pair(&self) -> Option<(Coord, Coord, u64)> {
+        let (first, last) = (self.by_sum.first()?, self.by_sum.last()?);
+        let by_difference = self.by_sum.iter().minmax_by_key(|(_, d, _)| *d).into_option()?;
+        let (first, second) = if last.0 - first.0 >= by_difference.1.1 - by_difference.0.1 {
+            (first.2, last.2)
+        } else {
+            (by_difference.0.2, by_difference.1.2)
+        };
+
+        Some((self.by_x[first].0, self.by_x[second].0, self.distance(first, second)))
+    }
+
+    pub fn diameter(&self) -> u64 {
+        self.farthest_pair().map_or(0, |(_, _, distance)| distance)
+    }
+}
+
+// sum of |a - b| over every pair - once sorted, each value is bigger than everything before it
+pub fn sum_of_differences(mut values: Vec<i64>) -> u64 {
+    values.sort_unstable();
+    let mut total = 0;
+    let mut prefix_sum = 0;
+    for (i, value) in values.into_iter().enumerate() {
+        total += i as i64 * value - prefix_sum;
+        prefix_sum += value;
     }
+
+    total as u64
 }
 
 pub fn parse_input(input: &str) -> Input {
@@ -54,26 +235,23 @@ pub fn parse_input(input: &str) -> Input {
     }
 
     let max_size = max_size.unwrap();
-    for y in 0..=max_size.y {
-        if galaxies.iter().filter(|c| c.y == y).next().is_none() {
-            blank_y.insert(y);
-        }
-    }
-
-    for x in 0..=max_size.x {
-        if galaxies.iter().filter(|c| c.x == x).next().is_none() {
-            blank_x.insert(x);
-        }
-    }
+    let occupied_x = galaxies.iter().map(|c| c.x).collect::<HashSet<_>>();
+    let occupied_y = galaxies.iter().map(|c| c.y).collect::<HashSet<_>>();
+    blank_x.extend((0..=max_size.x).filter(|x| !occupied_x.contains(x)));
+    blank_y.extend((0..=max_size.y).filter(|y| !occupied_y.contains(y)));
 
     Input { galaxies, blank_x, blank_y }
 }
 
 pub fn solve(input: &Input, expansion_factor: usize) -> usize {
-    input.galaxies.iter()
-        .tuple_combinations()
-        .map(|(f, s)| input.adjusted_distance_between(*f, *s, expansion_factor))
-        .sum()
+    solve_with(input, &Expansion::uniform(expansion_factor as u64)) as usize
+}
+
+// taxicab distance splits into the two axes, so we can total them up separately
+pub fn solve_with(input: &Input, expansion: &Expansion) -> u64 {
+    let galaxies = input.expand(expansion);
+    sum_of_differences(galaxies.iter().map(|c| c.x).collect())
+        + sum_of_differences(galaxies.iter().map(|c| c.y).collect())
 }
 
 fn main() {
@@ -100,4 +278,78 @@ pub fn test() {
     assert_eq!(solve(&input, 2), 374);
     assert_eq!(solve(&input, 10), 1030);
     assert_eq!(solve(&input, 100), 8410);
-}
\ No newline at end of file
+}
+
+#[test]
+pub fn test_uneven_expansion() {
+    let input = r"...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....";
+
+    let input = parse_input(input);
+    // the old pairwise sum, but with the axes expanding differently
+    let pairwise: u64 = input.galaxies.iter()
+        .tuple_combinations()
+        .map(|(f, s)| {
+            let blank_x_between = input.blank_x.range(f.x.min(s.x) .. f.x.max(s.x)).count() as u64;
+            let blank_y_between = input.blank_y.range(f.y.min(s.y) .. f.y.max(s.y)).count() as u64;
+            f.taxicab_distance_to(*s) + blank_x_between + 9 * blank_y_between
+        })
+        .sum();
+    let expansion = Expansion::new(AxisExpansion::Uniform(2), AxisExpansion::Uniform(10));
+    assert_eq!(solve_with(&input, &expansion), pairwise);
+
+    let weights = input.blank_x.iter().map(|x| (*x, 100)).collect();
+    let expansion = Expansion::new(AxisExpansion::Weighted(weights), AxisExpansion::Uniform(100));
+    assert_eq!(solve_with(&input, &expansion), 8410);
+}
+
+#[test]
+pub fn test_galaxy_index() {
+    let input = r"...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....";
+
+    let input = parse_input(input);
+    let expansion = Expansion::new(AxisExpansion::Uniform(3), AxisExpansion::Uniform(7));
+    let index = GalaxyIndex::new(&input, &expansion);
+    let galaxies = input.expand_galaxies(&expansion);
+    for (galaxy, expanded) in &galaxies {
+        let brute_force = galaxies.iter()
+            .filter(|(other, _)| other != galaxy)
+            .map(|(other, e)| (expanded.taxicab_distance_to(*e), other.y, other.x))
+            .sorted()
+            .map(|(d, y, x)| ((x, y).into(), d))
+            .collect::<Vec<(Coord, u64)>>();
+        assert_eq!(index.nearest(*galaxy, 3).unwrap(), brute_force[..3]);
+        assert_eq!(index.nearest(*galaxy, 20).unwrap(), brute_force);
+        let within = brute_force.iter().filter(|(_, d)| *d <= 12).copied().collect::<Vec<_>>();
+        assert_eq!(index.within(*galaxy, 12).unwrap(), within);
+    }
+
+    let diameter = galaxies.iter()
+        .tuple_combinations()
+        .map(|((_, f), (_, s))| f.taxicab_distance_to(*s))
+        .max()
+        .unwrap();
+    assert_eq!(index.diameter(), diameter);
+    let (first, second, distance) = index.farthest_pair().unwrap();
+    assert_eq!(distance, diameter);
+    let expanded = galaxies.iter().copied().collect::<HashMap<_, _>>();
+    assert_eq!(expanded[&first].taxicab_distance_to(expanded[&second]), diameter);
+    assert_eq!(index.nearest((0, 0).into(), 1), None);
+}
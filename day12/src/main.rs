@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use rand::Rng;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Line {
@@ -6,104 +6,359 @@ pub struct Line {
     numbers: Vec<u64>,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CountOverflow;
+
 impl Line {
-    pub fn expand(&self) -> Line {
+    pub fn expand(&self, copies: usize) -> Line {
         Line {
-            string_part: format!("{}?{}?{}?{}?{}", self.string_part.clone(), self.string_part.clone(), self.string_part.clone(), self.string_part.clone(), self.string_part.clone()),
-            numbers: self.numbers.repeat(5),
+            string_part: vec![self.string_part.as_str(); copies].join("?"),
+            numbers: self.numbers.repeat(copies),
         }
     }
 
-    // hashmap for memoizing the answers we see along the way - without this it's ungodly slow
-    pub fn count_options(&self, answers: &mut HashMap<(String, Vec<u64>), usize>) -> usize {
-        Self::find_fill_options(answers, &self.string_part, &self.numbers)
+    pub fn count_options(&self) -> Result<u128, CountOverflow> {
+        Ok(self.fill_counts()?[0][0])
     }
 
-    pub fn find_fill_options<'a>(answers: &mut HashMap<(String, Vec<u64>), usize>, remaining_string: &'a str, remaining_numbers: &'a [u64]) -> usize {
-        // first off: if we already know the answer because we've seen this combination of string-and-numbers before, return it.
-        let current_state = (remaining_string.to_owned(), remaining_numbers.to_vec());
-        if let Some(answer) = answers.get(&current_state) {
-            return *answer;
+    // dots_before[i] is the number of . in the first i chars, so we can check a run in one go
+    fn dots_before(&self) -> Vec<usize> {
+        let mut dots_before = vec![0];
+        for cell in self.string_part.bytes() {
+            dots_before.push(dots_before.last().unwrap() + usize::from(cell == b'.'));
         }
 
-        // otherwise, check if we're out of numbers to fill, and see if that makes sense for this string.
-        if remaining_numbers.is_empty() && remaining_string.contains('#') {
-            // if there are no more numbers left, but the remaining string has a # in, we've gone wrong => no answers.
-            answers.insert(current_state, 0);
-            return 0;
-        } else if remaining_numbers.is_empty() {
-            // if there are no more numbers left, and the remaining string has no # in, there's only one solution (replace all ? with .).
-            // this also covers the case where the remaining string is empty.
-            answers.insert(current_state, 1);
-            return 1;
+        dots_before
+    }
+
+    // can a run of # this long start here? the run itself can't contain a .
+    // and the char after it (if any) can't be a #
+    fn fits_at(&self, dots_before: &[usize], position: usize, run: usize) -> bool {
+        let end = position + run;
+        end < dots_before.len()
+            && dots_before[end] == dots_before[position]
+            && self.string_part.as_bytes().get(end) != Some(&b'#')
+    }
+
+    // where we carry on from after putting a run here (skipping the . that has to follow it)
+    fn after_run(&self, position: usize, run: usize) -> usize {
+        (position + run + 1).min(self.string_part.len())
+    }
+
+    // counts[position][group] is how many ways there are to fit the numbers from group onwards
+    // into the string from position onwards - filled in from the end of the string backwards
+    pub fn fill_counts(&self) -> Result<Vec<Vec<u128>>, CountOverflow> {
+        let cells = self.string_part.as_bytes();
+        let groups = self.numbers.iter().map(|n| *n as usize).collect::<Vec<_>>();
+        let length = cells.len();
+        let dots_before = self.dots_before();
+
+        let mut counts = vec![vec![0_u128; groups.len() + 1]; length + 1];
+        // with no numbers left there's exactly one answer, as long as there are no # left either
+        counts[length][groups.len()] = 1;
+        for position in (0..length).rev() {
+            counts[position][groups.len()] = if cells[position] == b'#' { 0 } else { counts[position + 1][groups.len()] };
+            for group in (0..groups.len()).rev() {
+                // make this char a . and leave the number for later...
+                let dot_option = if cells[position] == b'#' { 0 } else { counts[position + 1][group] };
+                // ...or start the number right here
+                let hash_option = if cells[position] != b'.' && self.fits_at(&dots_before, position, groups[group]) {
+                    counts[self.after_run(position, groups[group])][group + 1]
+                } else {
+                    0
+                };
+                counts[position][group] = dot_option.checked_add(hash_option).ok_or(CountOverflow)?;
+            }
         }
 
-        // so we have at least one number still to try and fill in to the string - do we have space for it?
-        if remaining_string.len() < remaining_numbers.iter().sum::<u64>() as usize + remaining_numbers.len() - 1 {
-            // if our remaining numbers (plus the mandatory . between them) would take up more space than we have left in the string
-            // then we've gone wrong => no answers.
-            answers.insert(current_state, 0);
-            return 0;
+        Ok(counts)
+    }
+
+    // the same as fill_counts but from the front: how many ways there are to fit the numbers
+    // before group into the string before position, leaving position free to start a new run
+    fn prefix_counts(&self) -> Result<Vec<Vec<u128>>, CountOverflow> {
+        let cells = self.string_part.as_bytes();
+        let length = cells.len();
+        let dots_before = self.dots_before();
+
+        let mut counts = vec![vec![0_u128; self.numbers.len() + 1]; length + 1];
+        counts[0][0] = 1;
+        for position in 0..length {
+            for group in 0..=self.numbers.len() {
+                let ways = counts[position][group];
+                if ways == 0 {
+                    continue;
+                }
+
+                if cells[position] != b'#' {
+                    let next = &mut counts[position + 1][group];
+                    *next = next.checked_add(ways).ok_or(CountOverflow)?;
+                }
+
+                if let Some(run) = self.numbers.get(group).map(|n| *n as usize) {
+                    if cells[position] != b'.' && self.fits_at(&dots_before, position, run) {
+                        let next = &mut counts[self.after_run(position, run)][group + 1];
+                        *next = next.checked_add(ways).ok_or(CountOverflow)?;
+                    }
+                }
+            }
         }
 
-        // we have at least one number to fit in, and space to fit it - let's see what the next char in the string is.
-        match remaining_string.chars().next().unwrap() {
-            '.' => {
-                // we can't fit the number here at the start since that's a . - skip ahead to the next ? or # and try again there
-                // if there aren't any more ? or # then there aren't any answers (since we know we have a number to fit in)
-                let answer = match remaining_string.find(['?', '#']) {
-                    Some(non_dot) => Self::find_fill_options(answers, &remaining_string[non_dot..], remaining_numbers),
-                    None => 0,
-                };
-                answers.insert(current_state, answer);
-                answer
-            },
-            '#' => {
-                // we already checked at the top: we have at least one number to fill in, and there is space for it
-                // since we have a # at the start, it must go here at the start - so check the next <number> chars to make sure they're all ? or #
-                let next_number = remaining_numbers[0] as usize;
-                if remaining_string.chars().take(next_number).any(|c| c == '.') {
-                    // found a .: this number can't go here after all, contradiction => no answers.
-                    answers.insert(current_state, 0);
-                    return 0;
+        Ok(counts)
+    }
+
+    pub fn arrangements(&self) -> Result<Arrangements<'_>, CountOverflow> {
+        Ok(Arrangements {
+            line: self,
+            counts: self.fill_counts()?,
+            dots_before: self.dots_before(),
+            stack: vec![(0, 0, Vec::new())],
+        })
+    }
+
+    // picks one of the valid arrangements, each as likely as any other
+    pub fn sample(&self, rng: &mut impl Rng) -> Result<Option<String>, CountOverflow> {
+        let counts = self.fill_counts()?;
+        if counts[0][0] == 0 {
+            return Ok(None);
+        }
+
+        let cells = self.string_part.as_bytes();
+        let (mut position, mut group) = (0, 0);
+        let mut filled = Vec::with_capacity(cells.len());
+        while position < cells.len() {
+            // weight the choice here by how many arrangements each option leads to
+            let dot_option = if cells[position] == b'#' { 0 } else { counts[position + 1][group] };
+            let pick = rng.gen_range(0..counts[position][group]);
+            if pick < dot_option {
+                filled.push(b'.');
+                position += 1;
+            } else {
+                let run = self.numbers[group] as usize;
+                filled.extend(std::iter::repeat_n(b'#', run));
+                if position + run < cells.len() {
+                    filled.push(b'.');
                 }
+                position = self.after_run(position, run);
+                group += 1;
+            }
+        }
 
-                // so the <next number> chars are either # or ?, and so we can fit the next number in here.
-                // skip ahead that many chars in the string (these will all become #)
-                // and skip ahead one number in the 'remaining numbers' list too
-                let new_remaining_string = &remaining_string[next_number..];
-                let new_remaining_numbers = &remaining_numbers[1..];
-
-                let answer = if new_remaining_string.is_empty() {
-                    // if we're now out of string, there's either one answer (no numbers left either), or no answer (still some numbers left)
-                    if new_remaining_numbers.is_empty() { 1 } else { 0 }
-                } else if new_remaining_string.starts_with('#') {
-                    // we just put a number here in so the next character has to be able to be a .
-                    // but the string says it has to be a # => contradiction, no answers.
-                    0
-                } else {
-                    // otherwise, we can make the next character a ., so skip it and recurse
-                    // try to fit the new smaller list of numbers into the new smaller string.
-                    Self::find_fill_options(answers, &new_remaining_string[1..], new_remaining_numbers)
-                };
+        Ok(Some(String::from_utf8(filled).unwrap()))
+    }
+
+    // for each ? in the string, how likely it is to be a # across all the valid arrangements
+    // (if there are no valid arrangements then there's nothing to report)
+    pub fn hash_probabilities(&self) -> Result<Vec<(usize, f64)>, CountOverflow> {
+        let (total, hashes) = self.hash_counts()?;
+        if total == 0 {
+            return Ok(Vec::new());
+        }
+
+        Ok(self.string_part.bytes()
+            .zip(hashes)
+            .enumerate()
+            .filter(|(_, (cell, _))| *cell == b'?')
+            .map(|(position, (_, hashes))| (position, hashes as f64 / total as f64))
+            .collect())
+    }
+
+    // the total number of arrangements, and how many of them have a # at each position
+    pub fn hash_counts(&self) -> Result<(u128, Vec<u128>), CountOverflow> {
+        let suffix_counts = self.fill_counts()?;
+        let total = suffix_counts[0][0];
+        let prefix_counts = self.prefix_counts()?;
+        let cells = self.string_part.as_bytes();
+        let dots_before = self.dots_before();
+        // every arrangement with a run starting at position covers the next few chars with #
+        // so add it on where the run starts and take it off again where it ends
+        // (the running totals are always real counts, so wrapping in between is harmless)
+        let mut hash_changes = vec![0_u128; cells.len() + 1];
+        for position in 0..cells.len() {
+            for (group, run) in self.numbers.iter().map(|n| *n as usize).enumerate() {
+                if cells[position] == b'.' || !self.fits_at(&dots_before, position, run) {
+                    continue;
+                }
+
+                let ways = prefix_counts[position][group]
+                    .checked_mul(suffix_counts[self.after_run(position, run)][group + 1])
+                    .ok_or(CountOverflow)?;
+                hash_changes[position] = hash_changes[position].wrapping_add(ways);
+                hash_changes[position + run] = hash_changes[position + run].wrapping_sub(ways);
+            }
+        }
+
+        let hashes = hash_changes[..cells.len()].iter()
+            .scan(0_u128, |hashes, change| {
+                *hashes = hashes.wrapping_add(*change);
+                Some(*hashes)
+            })
+            .collect();
+
+        Ok((total, hashes))
+    }
+}
+
+// every valid way of filling in a line, found depth-first and never going down a dead end
+#[derive(Clone, Debug)]
+pub struct Arrangements<'a> {
+    line: &'a Line,
+    counts: Vec<Vec<u128>>,
+    dots_before: Vec<usize>,
+    // (position, group, what we've filled in so far)
+    stack: Vec<(usize, usize, Vec<u8>)>,
+}
+
+impl<'a> Iterator for Arrangements<'a> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cells = self.line.string_part.as_bytes();
+        while let Some((position, group, mut filled)) = self.stack.pop() {
+            if self.counts[position][group] == 0 {
+                continue;
+            }
+
+            if group == self.line.numbers.len() {
+                // no numbers left, so everything else has to be a .
+                filled.resize(cells.len(), b'.');
+                return Some(String::from_utf8(filled).unwrap());
+            }
+
+            // push the . option first so we try putting runs as early as possible first
+            let run = self.line.numbers[group] as usize;
+            if cells[position] != b'#' {
+                let mut dot_option = filled.clone();
+                dot_option.push(b'.');
+                self.stack.push((position + 1, group, dot_option));
+            }
+
+            if cells[position] != b'.' && self.line.fits_at(&self.dots_before, position, run) {
+                filled.extend(std::iter::repeat_n(b'#', run));
+                if position + run < cells.len() {
+                    filled.push(b'.');
+                }
+                self.stack.push((self.line.after_run(position, run), group + 1, filled));
+            }
+        }
+
+        None
+    }
+}
+
+// a picross puzzle: the runs of # along each row (top to bottom) and each column (left to right)
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Nonogram {
+    rows: Vec<Vec<u64>>,
+    columns: Vec<Vec<u64>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NonogramSolution {
+    NoSolution,
+    Unique(Vec<String>),
+    // the first two solutions found - enough to show it isn't unique
+    Multiple(Vec<String>, Vec<String>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct Contradiction;
+
+impl Nonogram {
+    pub fn new(rows: Vec<Vec<u64>>, columns: Vec<Vec<u64>>) -> Self {
+        Self { rows, columns }
+    }
 
-                answers.insert(current_state, answer);
-                answer
+    pub fn solve(&self) -> NonogramSolution {
+        let mut solutions = self.search(Some(2));
+        match solutions.len() {
+            0 => NonogramSolution::NoSolution,
+            1 => NonogramSolution::Unique(solutions.remove(0)),
+            _ => {
+                let second = solutions.remove(1);
+                NonogramSolution::Multiple(solutions.remove(0), second)
             },
-            '?' => {
-                // we have two choices for the ? char, we can either make it a . or a #
-                // just try both of them and add up the possibilities for each 
-                let dot_option = remaining_string.replacen('?', ".", 1);
-                let hash_option = remaining_string.replacen('?', "#", 1);
-                let answer = Self::find_fill_options(answers, &dot_option, remaining_numbers)
-                    + Self::find_fill_options(answers, &hash_option, remaining_numbers);
-                answers.insert(current_state, answer);
-                answer
-                
+        }
+    }
+
+    pub fn all_solutions(&self) -> Vec<Vec<String>> {
+        self.search(None)
+    }
+
+    fn search(&self, limit: Option<usize>) -> Vec<Vec<String>> {
+        let grid = vec![vec![b'?'; self.columns.len()]; self.rows.len()];
+        let mut solutions = Vec::new();
+        self.search_from(grid, limit, &mut solutions);
+        solutions
+    }
+
+    fn search_from(&self, mut grid: Vec<Vec<u8>>, limit: Option<usize>, solutions: &mut Vec<Vec<String>>) {
+        if limit.is_some_and(|limit| solutions.len() >= limit) || self.propagate(&mut grid).is_err() {
+            return;
+        }
+
+        // line solving got as far as it can - if there's anything left, guess and carry on
+        let unknown = grid.iter()
+            .enumerate()
+            .find_map(|(y, row)| row.iter().position(|cell| *cell == b'?').map(|x| (x, y)));
+        match unknown {
+            None => solutions.push(grid.into_iter().map(|row| String::from_utf8(row).unwrap()).collect()),
+            Some((x, y)) => {
+                for guess in [b'#', b'.'] {
+                    let mut guessed = grid.clone();
+                    guessed[y][x] = guess;
+                    self.search_from(guessed, limit, solutions);
+                }
             },
-            _ => unreachable!(),
         }
     }
+
+    // fill in every cell that's the same in all the arrangements of its row or column,
+    // going back and forth until nothing else changes
+    fn propagate(&self, grid: &mut [Vec<u8>]) -> Result<(), Contradiction> {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (y, numbers) in self.rows.iter().enumerate() {
+                let cells = grid[y].clone();
+                for (x, cell) in Self::solve_line(&cells, numbers)?.into_iter().enumerate() {
+                    changed |= grid[y][x] != cell;
+                    grid[y][x] = cell;
+                }
+            }
+
+            for (x, numbers) in self.columns.iter().enumerate() {
+                let cells = grid.iter().map(|row| row[x]).collect::<Vec<_>>();
+                for (y, cell) in Self::solve_line(&cells, numbers)?.into_iter().enumerate() {
+                    changed |= grid[y][x] != cell;
+                    grid[y][x] = cell;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn solve_line(cells: &[u8], numbers: &[u64]) -> Result<Vec<u8>, Contradiction> {
+        let line = Line { string_part: String::from_utf8(cells.to_vec()).unwrap(), numbers: numbers.to_vec() };
+        // a line too big to count can still be filled in by guessing, so just leave it alone
+        let Ok((total, hashes)) = line.hash_counts() else {
+            return Ok(cells.to_vec());
+        };
+        if total == 0 {
+            return Err(Contradiction);
+        }
+
+        Ok(cells.iter()
+            .zip(hashes)
+            .map(|(cell, hashes)| match hashes {
+                _ if *cell != b'?' => *cell,
+                0 => b'.',
+                h if h == total => b'#',
+                _ => b'?',
+            })
+            .collect())
+    }
 }
 
 pub fn parse_input(input: &str) -> Vec<Line> {
@@ -114,20 +369,25 @@ pub fn parse_input(input: &str) -> Vec<Line> {
     }).collect()
 }
 
-pub fn part_1(lines: &[Line], answers: &mut HashMap<(String, Vec<u64>), usize>) -> usize {
-    lines.iter().map(|line| line.count_options(answers)).sum()
+pub fn solve(lines: &[Line], copies: usize) -> Result<u128, CountOverflow> {
+    lines.iter().try_fold(0_u128, |total, line| {
+        total.checked_add(line.expand(copies).count_options()?).ok_or(CountOverflow)
+    })
+}
+
+pub fn part_1(lines: &[Line]) -> Result<u128, CountOverflow> {
+    solve(lines, 1)
 }
 
-pub fn part_2(lines: &[Line], answers: &mut HashMap<(String, Vec<u64>), usize>) -> usize {
-    lines.iter().map(|line| line.expand().count_options(answers)).sum()
+pub fn part_2(lines: &[Line]) -> Result<u128, CountOverflow> {
+    solve(lines, 5)
 }
 
 fn main() {
     let input = include_str!("../input.txt");
     let lines = parse_input(input); 
-    let mut answers = HashMap::new();
-    println!("Part 1: {}", part_1(&lines, &mut answers));
-    println!("Part 2: {}", part_2(&lines, &mut answers));
+    println!("Part 1: {:?}", part_1(&lines));
+    println!("Part 2: {:?}", part_2(&lines));
 }
 
 #[test]
@@ -140,7 +400,71 @@ pub fn test_input() {
 ?###???????? 3,2,1";    
 
     let lines = parse_input(input);
-    let mut answers = HashMap::new();
-    assert_eq!(part_1(&lines, &mut answers), 21);
-    assert_eq!(part_2(&lines, &mut answers), 525152);
-}
\ No newline at end of file
+    assert_eq!(part_1(&lines), Ok(21));
+    assert_eq!(part_2(&lines), Ok(525152));
+}
+
+#[test]
+pub fn test_large_unfold() {
+    // n copies of "??" joined by "?" is 3n - 1 cells holding n separate #s: (2n choose n) ways
+    let lines = parse_input("?? 1");
+    assert_eq!(solve(&lines, 1), Ok(2));
+    assert_eq!(solve(&lines, 30), Ok(118264581564861424));
+    // (200 choose 25) - too big for a u64
+    assert_eq!(solve(&parse_input("???????? 1"), 25), Ok(45217131606152448808778187283008));
+    assert_eq!(solve(&parse_input("???????????? 1"), 40), Err(CountOverflow));
+}
+
+#[test]
+pub fn test_arrangements() {
+    let line = &parse_input("?###???????? 3,2,1")[0];
+    let arrangements = line.arrangements().unwrap().collect::<Vec<_>>();
+    assert_eq!(arrangements.len(), 10);
+    assert_eq!(arrangements[0], ".###.##.#...");
+    assert_eq!(arrangements[9], ".###....##.#");
+    assert!(arrangements.iter().all(|a| a.len() == 12 && a.split('.').filter(|r| !r.is_empty()).map(str::len).eq([3, 2, 1])));
+
+    use rand::{rngs::StdRng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(12);
+    for _ in 0..20 {
+        let sample = line.sample(&mut rng).unwrap().unwrap();
+        assert!(arrangements.contains(&sample));
+    }
+    assert_eq!(parse_input("#.# 3")[0].sample(&mut rng), Ok(None));
+
+    // the ?s either side of the ### are always . and the one after that is # in 4 of the 10
+    let probabilities = line.hash_probabilities().unwrap();
+    assert_eq!(probabilities[0], (0, 0.0));
+    assert_eq!(probabilities[1], (4, 0.0));
+    assert_eq!(probabilities[2], (5, 0.4));
+    for (position, probability) in probabilities {
+        let hashes = arrangements.iter().filter(|a| a.as_bytes()[position] == b'#').count();
+        assert_eq!(probability, hashes as f64 / 10.0);
+    }
+}
+
+#[test]
+pub fn test_nonogram() {
+    let nonogram = Nonogram::new(
+        vec![vec![3], vec![1, 1], vec![5], vec![1, 1], vec![1, 1]],
+        vec![vec![4], vec![1, 1], vec![1, 1], vec![1, 1], vec![4]],
+    );
+    assert_eq!(nonogram.solve(), NonogramSolution::Unique(vec![
+        ".###.".to_owned(),
+        "#...#".to_owned(),
+        "#####".to_owned(),
+        "#...#".to_owned(),
+        "#...#".to_owned(),
+    ]));
+
+    // the two diagonals both fit
+    let nonogram = Nonogram::new(vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+    assert!(matches!(nonogram.solve(), NonogramSolution::Multiple(_, _)));
+    assert_eq!(nonogram.all_solutions(), vec![
+        vec!["#.".to_owned(), ".#".to_owned()],
+        vec![".#".to_owned(), "#.".to_owned()],
+    ]);
+
+    let nonogram = Nonogram::new(vec![vec![2], vec![]], vec![vec![2], vec![]]);
+    assert_eq!(nonogram.solve(), NonogramSolution::NoSolution);
+}
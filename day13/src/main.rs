@@ -1,6 +1,3 @@
-use std::collections::HashSet;
-use itertools::Itertools;
-
 #[derive(Copy, Clone, Hash, PartialEq, Eq, Debug)]
 pub enum Ground { Ash, Rock, }
 
@@ -15,10 +12,45 @@ impl From<char> for Ground {
 }
 
 #[derive(Copy, Clone, Hash, PartialEq, Eq, Debug)]
-pub enum PairMatchState {
-    Identical,
-    Fixable,
-    TooDifferent,
+pub enum Reflection {
+    // the number of rows above the mirror
+    Horizontal(usize),
+    // the number of columns to the left of the mirror
+    Vertical(usize),
+}
+
+impl Reflection {
+    pub fn summary(&self) -> usize {
+        match self {
+            Self::Horizontal(rows) => 100 * rows,
+            Self::Vertical(columns) => *columns,
+        }
+    }
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+pub struct Mirror {
+    reflection: Reflection,
+    // (x, y) of the cells to flip - each is one of a mismatched pair, so flipping
+    // its reflection instead would do just as well
+    smudges: Vec<(usize, usize)>,
+}
+
+impl Mirror {
+    pub fn reflection(&self) -> Reflection {
+        self.reflection
+    }
+
+    pub fn smudges(&self) -> &[(usize, usize)] {
+        &self.smudges
+    }
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+pub enum SolveError {
+    // the index of the pattern, and every mirror we found in it
+    NoMirror(usize),
+    SeveralMirrors(usize, Vec<Mirror>),
 }
 
 #[derive(Clone, Debug)]
@@ -31,59 +63,35 @@ impl Pattern {
             .collect())
     }
 
-    // finds horizontal lines only (transpose to find the others)
-    pub fn find_symmetry(&self) -> Option<usize> {
-        // every row except the last one could be the start of a symmetry
-        let mut potential_symmetries = (0..self.0.len() - 1).collect::<Vec<_>>();
-
-        potential_symmetries.retain(|p| {
-            (0 ..= *p).rev().zip(p + 1 .. self.0.len()).all(|(l, r)| self.0[l] == self.0[r])
-        });
-
-        // don't forget to adjust the indexes (we want to start at 1)
-        // and take the first (only) one
-        potential_symmetries.iter().map(|x| x + 1).next()
+    // every mirror line (either way) that needs exactly this many cells flipping to work
+    pub fn find_symmetries(&self, smudges: usize) -> Vec<Mirror> {
+        let horizontal = self.find_horizontal_symmetries(smudges)
+            .into_iter()
+            .map(|(rows, smudges)| Mirror { reflection: Reflection::Horizontal(rows), smudges });
+        // the transpose has x and y the other way round, so swap them back
+        let vertical = self.transpose().find_horizontal_symmetries(smudges)
+            .into_iter()
+            .map(|(columns, smudges)| Mirror {
+                reflection: Reflection::Vertical(columns),
+                smudges: smudges.into_iter().map(|(x, y)| (y, x)).collect(),
+            });
+
+        horizontal.chain(vertical).collect()
     }
 
-    pub fn find_near_symmetry(&self) -> Option<usize> {
-        let mut potential_near_symmetries = (0..self.0.len() - 1).collect::<Vec<_>>();
-
-        // have we already fixed a smudge for the symmetry starting at this offset?
-        let mut smudge_fixed = HashSet::new();
-
-        potential_near_symmetries.retain(|p| {
-            for (l, r) in (0 ..= *p).rev().zip(p + 1 .. self.0.len()) {
-                match Self::pair_fixable(&self.0[l], &self.0[r]) {
-                    PairMatchState::Identical => { },
-                    PairMatchState::Fixable => {
-                        // remember we can fix up a row from this start point
-                        // but if we've already used our smudge up, we can't do it again
-                        if !smudge_fixed.insert(*p) {
-                            return false;
-                        }
-                    },
-                    PairMatchState::TooDifferent => return false,
+    fn find_horizontal_symmetries(&self, smudges: usize) -> Vec<(usize, Vec<(usize, usize)>)> {
+        (0..self.0.len() - 1).filter_map(|p| {
+            let mut differences = Vec::new();
+            for (l, r) in (0 ..= p).rev().zip(p + 1 .. self.0.len()) {
+                differences.extend((0..self.0[l].len()).filter(|x| self.0[l][*x] != self.0[r][*x]).map(|x| (x, l)));
+                if differences.len() > smudges {
+                    return None;
                 }
             }
 
-            true
-        });
-
-        // but then: we _also_ only want to retain the rows that needed a smudge to get fixed
-        potential_near_symmetries.retain(|p| smudge_fixed.contains(p));
-
-        // don't forget to adjust the indexes (we want to start at 1)
-        potential_near_symmetries.iter().map(|x| x + 1).next()     
-    }
-
-    // the pair of rows is fixable if there's only one component that differs
-    pub fn pair_fixable<T>(first: &[T], second: &[T]) -> PairMatchState 
-        where T : Eq {
-            match first.iter().zip(second.iter()).filter(|(f, s)| f != s).count() {
-                0 => PairMatchState::Identical,
-                1 => PairMatchState::Fixable,
-                _ => PairMatchState::TooDifferent,
-            }
+            // again, adjust the index so it starts at 1
+            (differences.len() == smudges).then(|| (p + 1, differences))
+        }).collect()
     }
 }
 
@@ -99,29 +107,30 @@ pub fn parse_input(input: &str) -> Vec<Pattern> {
     result
 }
 
-pub fn solve(patterns: &[Pattern], selector: fn(&Pattern) -> Option<usize>) -> usize {
-    patterns.iter().map(|p|
-        match (selector(p), selector(&p.transpose())) {
-            (Some(h), None) => 100 * h,
-            (None, Some(v)) => v,
-            _ => unreachable!(),
+pub fn solve(patterns: &[Pattern], smudges: usize) -> Result<usize, SolveError> {
+    patterns.iter().enumerate().map(|(index, p)| {
+        let mut mirrors = p.find_symmetries(smudges);
+        match mirrors.len() {
+            0 => Err(SolveError::NoMirror(index)),
+            1 => Ok(mirrors.remove(0).reflection().summary()),
+            _ => Err(SolveError::SeveralMirrors(index, mirrors)),
         }
-    ).sum()
+    }).sum()
 }
 
-pub fn part_1(patterns: &[Pattern]) -> usize {
-    solve(patterns, Pattern::find_symmetry)
+pub fn part_1(patterns: &[Pattern]) -> Result<usize, SolveError> {
+    solve(patterns, 0)
 }
 
-pub fn part_2(patterns: &[Pattern]) -> usize {
-    solve(patterns, Pattern::find_near_symmetry)
+pub fn part_2(patterns: &[Pattern]) -> Result<usize, SolveError> {
+    solve(patterns, 1)
 }
 
 fn main() {
     let input = include_str!("../input.txt");
     let patterns = parse_input(input);
-    println!("Part 1: {}", part_1(&patterns));
-    println!("Part 2: {}", part_2(&patterns));
+    println!("Part 1: {:?}", part_1(&patterns));
+    println!("Part 2: {:?}", part_2(&patterns));
 }
 
 #[test]
@@ -143,6 +152,33 @@ pub fn test() {
 #....#..#";
 
     let patterns = parse_input(input);
-    assert_eq!(part_1(&patterns), 405);
-    assert_eq!(part_2(&patterns), 400);
+    assert_eq!(part_1(&patterns), Ok(405));
+    assert_eq!(part_2(&patterns), Ok(400));
+}
+
+#[test]
+pub fn test_smudges() {
+    let input = r"#.##..##.
+..#.##.#.
+##......#
+##......#
+..#.##.#.
+..##..##.
+#.#.##.#.";
+
+    let patterns = parse_input(input);
+    assert_eq!(patterns[0].find_symmetries(0), vec![Mirror { reflection: Reflection::Vertical(5), smudges: vec![] }]);
+    assert_eq!(patterns[0].find_symmetries(1), vec![Mirror { reflection: Reflection::Horizontal(3), smudges: vec![(0, 0)] }]);
+    assert_eq!(patterns[0].find_symmetries(2), vec![Mirror { reflection: Reflection::Vertical(1), smudges: vec![(0, 0), (0, 6)] }]);
+    let mirror = &patterns[0].find_symmetries(1)[0];
+    assert_eq!(mirror.reflection(), Reflection::Horizontal(3));
+    assert_eq!(mirror.smudges(), [(0, 0)]);
+    assert_eq!(solve(&patterns, 20), Err(SolveError::NoMirror(0)));
+
+    // a blank square is symmetric every which way
+    let patterns = parse_input("..\n..");
+    assert_eq!(solve(&patterns, 0), Err(SolveError::SeveralMirrors(0, vec![
+        Mirror { reflection: Reflection::Horizontal(1), smudges: vec![] },
+        Mirror { reflection: Reflection::Vertical(1), smudges: vec![] },
+    ])));
 }
\ No newline at end of file
@@ -1,100 +1,201 @@
-use std::collections::{HashSet, HashMap, BTreeSet};
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Direction {
+    North, West, South, East,
+}
 
-#[derive(PartialEq, Eq, Copy, Clone, Hash, Debug, Ord, PartialOrd)]
-pub struct Coord {
-    x: i64,
-    y: i64,
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ProgramError {
+    NoTilts,
+    UnknownTilt(char),
+    BadRepetitions,
 }
 
-impl From<(i64, i64)> for Coord {
-    fn from((x, y): (i64, i64)) -> Self {
-        Coord { x, y }
+impl TryFrom<char> for Direction {
+    type Error = ProgramError;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            'N' => Ok(Self::North),
+            'W' => Ok(Self::West),
+            'S' => Ok(Self::South),
+            'E' => Ok(Self::East),
+            _ => Err(ProgramError::UnknownTilt(value)),
+        }
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
-pub enum Direction {
-    North, West, South, East,
+// a sequence of tilts, run over and over: written like "NWSE*1000000000" (or just "NNESW" to run once)
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SpinProgram {
+    tilts: Vec<Direction>,
+    repetitions: u64,
+}
+
+impl std::str::FromStr for SpinProgram {
+    type Err = ProgramError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tilts, repetitions) = match s.trim().split_once('*') {
+            Some((tilts, repetitions)) => (tilts, repetitions.trim().parse().map_err(|_| ProgramError::BadRepetitions)?),
+            None => (s.trim(), 1),
+        };
+        let tilts = tilts.trim().chars().map(Direction::try_from).collect::<Result<Vec<_>, _>>()?;
+        if tilts.is_empty() {
+            return Err(ProgramError::NoTilts);
+        }
+
+        Ok(Self { tilts, repetitions })
+    }
+}
+
+// one bit per cell, row by row, with each row padded out to a whole number of words
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Bitboard {
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    words: Vec<u64>,
+}
+
+impl Bitboard {
+    pub fn new(width: usize, height: usize) -> Self {
+        let words_per_row = width.div_ceil(64);
+        Self { width, height, words_per_row, words: vec![0; words_per_row * height] }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.row(y)[x / 64] & (1 << (x % 64)) != 0
+    }
+
+    pub fn set(&mut self, x: usize, y: usize) {
+        let words_per_row = self.words_per_row;
+        self.words[y * words_per_row + x / 64] |= 1 << (x % 64);
+    }
+
+    pub fn row(&self, y: usize) -> &[u64] {
+        &self.words[y * self.words_per_row .. (y + 1) * self.words_per_row]
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0 .. self.height).flat_map(move |y| set_bits(self.row(y)).map(move |x| (x, y)))
+    }
+
+    pub fn count_in_row(&self, y: usize) -> u32 {
+        self.row(y).iter().map(|w| w.count_ones()).sum()
+    }
+
+    // splitmix-style mixing of every word - good enough to tell states apart
+    pub fn state_hash(&self) -> u64 {
+        self.words.iter().fold(0x9e37_79b9_7f4a_7c15, |hash, word| {
+            let mut mixed = (hash ^ word).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            mixed ^= mixed >> 31;
+            mixed.wrapping_mul(0x94d0_49bb_1331_11eb).rotate_left(17)
+        })
+    }
+}
+
+// the index of every set bit in the row, lowest first
+fn set_bits(row: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    row.iter().enumerate().flat_map(|(w, word)| {
+        let mut word = *word;
+        std::iter::from_fn(move || (word != 0).then(|| {
+            let bit = word.trailing_zeros() as usize;
+            word &= word - 1;
+            w * 64 + bit
+        }))
+    })
+}
+
+// mask of the bits from..to within the word starting at bit `start`
+fn word_mask(start: usize, from: usize, to: usize) -> u64 {
+    let from = from.clamp(start, start + 64) - start;
+    let to = to.clamp(start, start + 64) - start;
+    if from >= to {
+        0
+    } else if to - from == 64 {
+        !0
+    } else {
+        ((1 << (to - from)) - 1) << from
+    }
+}
+
+fn count_in_range(row: &[u64], from: usize, to: usize) -> usize {
+    (from / 64 .. to.div_ceil(64))
+        .map(|w| (row[w] & word_mask(w * 64, from, to)).count_ones() as usize)
+        .sum()
+}
+
+fn set_range(row: &mut [u64], from: usize, to: usize) {
+    for (w, word) in row.iter_mut().enumerate().take(to.div_ceil(64)).skip(from / 64) {
+        *word |= word_mask(w * 64, from, to);
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Input {
-    cube_rocks: HashSet<Coord>,
-    cube_rocks_by_x_y: HashMap<i64, BTreeSet<i64>>,
-    cube_rocks_by_y_x: HashMap<i64, BTreeSet<i64>>,
-    round_rocks: BTreeSet<Coord>,
-    max_size: Coord,
+    cube_rocks: Bitboard,
+    round_rocks: Bitboard,
 }
 
 impl Input {
-    pub fn tilt(self, direction: Direction) -> Input {
+    pub fn tilt(mut self, direction: Direction) -> Input {
         // cube rocks don't move, only round rocks do.
-        let mut new_round_rocks: BTreeSet<Coord> = BTreeSet::new();
-        let mut new_round_rocks_by_x_y: HashMap<i64, BTreeSet<i64>> = HashMap::new();
-        let mut new_round_rocks_by_y_x: HashMap<i64, BTreeSet<i64>> = HashMap::new();
-
-        let Coord { x: max_x, y: max_y} = self.max_size;
         match direction {
-            Direction::North => {
-                for y in 0 ..= max_y {
-                    for x in 0 ..= max_x {
-                        let current_coord = (x, y).into();
-                        if self.round_rocks.contains(&current_coord) {
-                            let nearest_cube_rock = *self.cube_rocks_by_x_y.get(&x).and_then(|s| s.range(..y).max()).unwrap_or(&-1);
-                            let nearest_new_round_rock = *new_round_rocks_by_x_y.get(&x).and_then(|s| s.range(..y).max()).unwrap_or(&-1);
-
-                            new_round_rocks.insert((x, nearest_cube_rock.max(nearest_new_round_rock) + 1).into());
-                            new_round_rocks_by_x_y.entry(x).or_insert(BTreeSet::new()).insert(nearest_cube_rock.max(nearest_new_round_rock) + 1);
-                        }
-                    }
-                }
-            },
-            Direction::West => {
-                for x in 0 ..= max_x {
-                    for y in 0 ..= max_y {
-                        let current_coord = (x, y).into();
-                        if self.round_rocks.contains(&current_coord) {
-                            let nearest_cube_rock = *self.cube_rocks_by_y_x.get(&y).and_then(|s| s.range(..x).max()).unwrap_or(&-1);
-                            let nearest_new_round_rock = *new_round_rocks_by_y_x.get(&y).and_then(|s| s.range(..x).max()).unwrap_or(&-1);
-
-                            new_round_rocks.insert((nearest_cube_rock.max(nearest_new_round_rock) + 1, y).into());
-                            new_round_rocks_by_y_x.entry(y).or_insert(BTreeSet::new()).insert(nearest_cube_rock.max(nearest_new_round_rock) + 1);
-
-                        }
-                    }
-                }
-            },
-            Direction::South => {
-                for y in (0 ..= max_y).rev() {
-                    for x in 0 ..= max_x {
-                        let current_coord = (x, y).into();
-                        if self.round_rocks.contains(&current_coord) {
-                            let nearest_cube_rock = *self.cube_rocks_by_x_y.get(&x).and_then(|s| s.range(y + 1 ..).min()).unwrap_or(&(self.max_size.y + 1));
-                            let nearest_new_round_rock = *new_round_rocks_by_x_y.get(&x).and_then(|s| s.range(y + 1 ..).min()).unwrap_or(&(self.max_size.y + 1));
-
-                            new_round_rocks.insert((x, nearest_cube_rock.min(nearest_new_round_rock) - 1).into());
-                            new_round_rocks_by_x_y.entry(x).or_insert(BTreeSet::new()).insert(nearest_cube_rock.min(nearest_new_round_rock) - 1);
-                        }
-                    }
+            Direction::North => self.tilt_vertically(false),
+            Direction::South => self.tilt_vertically(true),
+            Direction::West => self.tilt_horizontally(false),
+            Direction::East => self.tilt_horizontally(true),
+        }
+
+        self
+    }
+
+    // every row at once: go from the edge we're tilting towards, and let each row's rocks
+    // move one row at a time across the (already settled) rows until they hit something
+    fn tilt_vertically(&mut self, towards_south: bool) {
+        let Bitboard { height, words_per_row, .. } = self.round_rocks;
+        let rows = if towards_south { (0 .. height).rev().collect::<Vec<_>>() } else { (0 .. height).collect() };
+        let words = &mut self.round_rocks.words;
+        let cubes = &self.cube_rocks.words;
+        for (settled, y) in rows.iter().enumerate().skip(1) {
+            for w in 0 .. words_per_row {
+                let mut moving = words[y * words_per_row + w];
+                words[y * words_per_row + w] = 0;
+                let mut current = settled;
+                while moving != 0 && current > 0 {
+                    let (here, next) = (rows[current], rows[current - 1]);
+                    let blocked = cubes[next * words_per_row + w] | words[next * words_per_row + w];
+                    words[here * words_per_row + w] |= moving & blocked;
+                    moving &= !blocked;
+                    current -= 1;
                 }
-            },
-            Direction::East => {
-                for x in (0 ..= max_x).rev() {
-                    for y in 0 ..= max_y {
-                        let current_coord = (x, y).into();
-                        if self.round_rocks.contains(&current_coord) {
-                            let nearest_cube_rock = *self.cube_rocks_by_y_x.get(&y).and_then(|s| s.range(x + 1 ..).min()).unwrap_or(&(self.max_size.x + 1));
-                            let nearest_new_round_rock = *new_round_rocks_by_y_x.get(&y).and_then(|s| s.range(x + 1 ..).min()).unwrap_or(&(self.max_size.x + 1));
-
-                            new_round_rocks.insert((nearest_cube_rock.min(nearest_new_round_rock) - 1, y).into());
-                            new_round_rocks_by_y_x.entry(y).or_insert(BTreeSet::new()).insert(nearest_cube_rock.min(nearest_new_round_rock) - 1);
-                        }
-                    }
+                words[rows[current] * words_per_row + w] |= moving;
+            }
+        }
+    }
+
+    // one row at a time: count the rocks between each pair of cube rocks, and pile them up at one end
+    fn tilt_horizontally(&mut self, towards_east: bool) {
+        let Bitboard { width, height, words_per_row, .. } = self.round_rocks;
+        let mut new_round_rocks = Bitboard::new(width, height);
+        for y in 0 .. height {
+            let round_row = self.round_rocks.row(y);
+            let new_row = &mut new_round_rocks.words[y * words_per_row .. (y + 1) * words_per_row];
+            let cube_positions = set_bits(self.cube_rocks.row(y));
+
+            let mut start = 0;
+            for end in cube_positions.chain(std::iter::once(width)) {
+                let rocks = count_in_range(round_row, start, end);
+                if towards_east {
+                    set_range(new_row, end - rocks, end);
+                } else {
+                    set_range(new_row, start, start + rocks);
                 }
-            },
+                start = end + 1;
+            }
         }
 
-        Input { round_rocks: new_round_rocks, ..self }
+        self.round_rocks = new_round_rocks;
     }
 
     pub fn cycle(self) -> Input {
@@ -106,21 +207,68 @@ impl Input {
 
     }
 
+    // runs through the program's tilts once, ignoring its repetitions
+    pub fn run(self, program: &SpinProgram) -> Input {
+        program.tilts.iter().fold(self, |state, direction| state.tilt(*direction))
+    }
+
     pub fn north_weight(&self) -> i64 {
-        // the weight of a round rock is max_size.y + 1 - y height?
-        self.round_rocks.iter().map(|c| self.max_size.y + 1 - c.y).sum()
+        // the weight of a round rock is how many rows there are from it to the south edge
+        let height = self.round_rocks.height;
+        (0 .. height).map(|y| (height - y) as i64 * self.round_rocks.count_in_row(y) as i64).sum()
+    }
+
+    // how much a round rock at (x, y) weighs on this edge: the number of rows (or columns)
+    // from it to the opposite edge
+    fn rock_load(&self, edge: Direction, (x, y): (usize, usize)) -> i64 {
+        let Bitboard { width, height, .. } = self.round_rocks;
+        (match edge {
+            Direction::North => height - y,
+            Direction::South => y + 1,
+            Direction::West => width - x,
+            Direction::East => x + 1,
+        }) as i64
+    }
+
+    pub fn load(&self, edge: Direction) -> i64 {
+        self.round_rocks.positions().map(|p| self.rock_load(edge, p)).sum()
+    }
+
+    pub fn row_loads(&self, edge: Direction) -> Vec<i64> {
+        let mut loads = vec![0; self.round_rocks.height];
+        for (x, y) in self.round_rocks.positions() {
+            loads[y] += self.rock_load(edge, (x, y));
+        }
+
+        loads
+    }
+
+    pub fn column_loads(&self, edge: Direction) -> Vec<i64> {
+        let mut loads = vec![0; self.round_rocks.width];
+        for (x, y) in self.round_rocks.positions() {
+            loads[x] += self.rock_load(edge, (x, y));
+        }
+
+        loads
+    }
+
+    pub fn state_hash(&self) -> u64 {
+        self.round_rocks.state_hash()
+    }
+
+    // the cube rocks never move, so compare the round ones outright (a hash could collide)
+    pub fn fingerprint(&self) -> Bitboard {
+        self.round_rocks.clone()
     }
 }
 
 impl std::fmt::Display for Input {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let Coord { x: max_x, y: max_y} = self.max_size;
-        for y in 0 ..= max_y {
-            for x in 0 ..= max_x {
-                let current_coord = (x, y).into();
-                if self.cube_rocks.contains(&current_coord) {
+        for y in 0 .. self.round_rocks.height {
+            for x in 0 .. self.round_rocks.width {
+                if self.cube_rocks.get(x, y) {
                     write!(f, "#")?;
-                } else if self.round_rocks.contains(&current_coord) {
+                } else if self.round_rocks.get(x, y) {
                     write!(f, "O")?;
                 } else {
                     write!(f, ".")?;
@@ -135,67 +283,38 @@ impl std::fmt::Display for Input {
 
 
 pub fn parse_input(input: &str) -> Input {
-    let mut round_rocks: BTreeSet<Coord> = BTreeSet::new();
-    let mut cube_rocks: HashSet<Coord> = HashSet::new();
-    let mut max_size = None;
+    let height = input.lines().count();
+    let width = input.lines().next().unwrap().len();
+    let mut round_rocks = Bitboard::new(width, height);
+    let mut cube_rocks = Bitboard::new(width, height);
 
     for (y, line) in input.lines().enumerate() {
         for (x, c) in line.chars().enumerate() {
-            let current_coord = (x as i64, y as i64).into();
             if c == '#' {
-                cube_rocks.insert(current_coord);
+                cube_rocks.set(x, y);
             } else if c == 'O' {
-                round_rocks.insert(current_coord);
+                round_rocks.set(x, y);
             }
-
-            max_size = Some(current_coord);
         }
     }
 
-    let max_size = max_size.unwrap();
-
-    let mut cube_rocks_by_x_y = HashMap::new();
-    let mut cube_rocks_by_y_x = HashMap::new();
-    for c in &cube_rocks {
-        cube_rocks_by_x_y.entry(c.x).or_insert(BTreeSet::new()).insert(c.y);
-        cube_rocks_by_y_x.entry(c.y).or_insert(BTreeSet::new()).insert(c.x);
-    }
-
-    Input { round_rocks, cube_rocks, cube_rocks_by_x_y, cube_rocks_by_y_x, max_size }
+    Input { round_rocks, cube_rocks }
 }
 
 pub fn part_1(input: &Input) -> i64 {
     input.clone().tilt(Direction::North).north_weight()
 }
 
-pub fn find_cycle(input: &Input) -> (Input, i64) {
-    let number_of_cycles_to_run = 1_000_000_000;
-    let mut states = HashMap::new();
-    let mut state = input.clone();
-    for cycle in 1 ..= number_of_cycles_to_run {
-        state = state.cycle();
-        if let Some(previous_cycle) = states.insert(state.round_rocks.clone(), cycle) {
-            println!("Found state after cycle {} that matches cycle {}", cycle, previous_cycle);
-
-            let cycle_length = cycle - previous_cycle;
-            let remaining_period_to_fill = number_of_cycles_to_run - previous_cycle;
-            let number_to_run_after_cycle = remaining_period_to_fill % cycle_length;
-
-            return (state, number_to_run_after_cycle);
-        }
-    }
+pub fn spin(input: &Input, cycles: u64) -> Input {
+    cycles::state_at(input, |state| state.clone().cycle(), Input::fingerprint, cycles)
+}
 
-    unreachable!();
+pub fn run_program(input: &Input, program: &SpinProgram) -> Input {
+    cycles::state_at(input, |state| state.clone().run(program), Input::fingerprint, program.repetitions)
 }
 
 pub fn part_2(input: &Input) -> i64 {
-    let (repeated_state, remaining_cycles) = find_cycle(input);
-    let mut state = repeated_state;
-    for _ in 1 ..= remaining_cycles {
-        state = state.cycle();
-    }
-
-    state.north_weight()
+    spin(input, 1_000_000_000).north_weight()
 }
 
 fn main() {
@@ -235,4 +354,85 @@ O.#..O.#.#
 #OO..#....";
     let input = parse_input(input);
     assert_eq!(part_2(&input), 64);
+
+    // the same rocks hash the same, wherever they came from
+    assert_eq!(spin(&input, 3).state_hash(), spin(&input, 10).state_hash());
+    assert_ne!(input.state_hash(), spin(&input, 1).state_hash());
+}
+
+#[test]
+pub fn test_wide_dish() {
+    // spread some rocks around a dish wider than one word, and tilt it the slow way too
+    let (width, height) = (150, 70);
+    let mut seed = 12345_u64;
+    let mut cells = vec![vec!['.'; width]; height];
+    for cell in cells.iter_mut().flatten() {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        *cell = match seed >> 60 {
+            0 | 1 => '#',
+            2..=6 => 'O',
+            _ => '.',
+        };
+    }
+
+    let tilt_slowly = |cells: &mut Vec<Vec<char>>, dx: i64, dy: i64| {
+        let mut moved = true;
+        while moved {
+            moved = false;
+            for y in 0 .. height as i64 {
+                for x in 0 .. width as i64 {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if cells[y as usize][x as usize] == 'O' && (0 .. width as i64).contains(&nx) && (0 .. height as i64).contains(&ny)
+                        && cells[ny as usize][nx as usize] == '.' {
+                        cells[ny as usize][nx as usize] = 'O';
+                        cells[y as usize][x as usize] = '.';
+                        moved = true;
+                    }
+                }
+            }
+        }
+    };
+
+    let render = |cells: &Vec<Vec<char>>| cells.iter().map(|row| row.iter().collect::<String>() + "\n").collect::<String>();
+    let mut input = parse_input(&render(&cells));
+    assert_eq!(input.to_string(), render(&cells));
+    for (direction, dx, dy) in [(Direction::North, 0, -1), (Direction::West, -1, 0), (Direction::South, 0, 1), (Direction::East, 1, 0)] {
+        input = input.tilt(direction);
+        tilt_slowly(&mut cells, dx, dy);
+        assert_eq!(input.to_string(), render(&cells));
+    }
+}
+
+#[test]
+pub fn test_programs() {
+    let input = r"O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....";
+    let input = parse_input(input);
+    assert_eq!(run_program(&input, &"NWSE*1000000000".parse().unwrap()).north_weight(), 64);
+    assert_eq!(run_program(&input, &"NWSE * 3".parse().unwrap()).to_string(), spin(&input, 3).to_string());
+    assert_eq!(run_program(&input, &"N".parse().unwrap()).load(Direction::North), 136);
+
+    let tilted = run_program(&input, &"N".parse().unwrap());
+    assert_eq!(tilted.column_loads(Direction::North)[0], 10 + 9 + 8 + 7);
+    assert_eq!(tilted.row_loads(Direction::North)[0], 5 * 10);
+
+    // tilting the same way twice running does nothing more
+    let state = run_program(&input, &"NNESW*1000000000000000000".parse().unwrap());
+    assert_eq!(state.to_string(), run_program(&input, &"NESW*1000000000000000000".parse().unwrap()).to_string());
+    for edge in [Direction::North, Direction::West, Direction::South, Direction::East] {
+        assert_eq!(state.row_loads(edge).iter().sum::<i64>(), state.load(edge));
+        assert_eq!(state.column_loads(edge).iter().sum::<i64>(), state.load(edge));
+    }
+
+    assert_eq!("NWX".parse::<SpinProgram>(), Err(ProgramError::UnknownTilt('X')));
+    assert_eq!("*5".parse::<SpinProgram>(), Err(ProgramError::NoTilts));
+    assert_eq!("N*lots".parse::<SpinProgram>(), Err(ProgramError::BadRepetitions));
 }
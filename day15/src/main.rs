@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 pub fn hash(input: &str) -> u64 {
     input.bytes().fold(0, |acc, b| ((acc + b as u64) * 17) % 256)
 }
@@ -8,54 +6,249 @@ pub fn part_1(input: &str) -> u64 {
     input.split(',').map(hash).sum()
 }
 
+const BOX_COUNT: usize = 256;
+
+fn default_hash<K: AsRef<str>>(key: &K) -> u64 {
+    hash(key.as_ref())
+}
+
+// the HASHMAP from the puzzle: 256 boxes, each holding its lenses in the order they went in
+#[derive(Clone, Debug)]
+pub struct LensHashMap<K, V> {
+    boxes: Vec<Vec<(K, V)>>,
+    hasher: fn(&K) -> u64,
+}
+
+impl<K: AsRef<str> + Eq, V> LensHashMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(default_hash::<K>)
+    }
+}
+
+impl<K: AsRef<str> + Eq, V> Default for LensHashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq, V> LensHashMap<K, V> {
+    // whatever the hasher returns gets wrapped round to fit the boxes
+    pub fn with_hasher(hasher: fn(&K) -> u64) -> Self {
+        Self { boxes: (0 .. BOX_COUNT).map(|_| Vec::new()).collect(), hasher }
+    }
+
+    pub fn box_for(&self, key: &K) -> usize {
+        ((self.hasher)(key) % BOX_COUNT as u64) as usize
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        let box_number = self.box_for(&key);
+        let lenses = &mut self.boxes[box_number];
+        match lenses.iter().position(|(k, _)| k == &key) {
+            Some(slot) => Entry::Occupied(OccupiedEntry { lenses, slot }),
+            None => Entry::Vacant(VacantEntry { lenses, key }),
+        }
+    }
+
+    // replaces the lens in place if there's already one with this label, otherwise goes on the end
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            },
+        }
+    }
+
+    // everything behind the removed lens moves forward a slot
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let box_number = self.box_for(key);
+        let lenses = &mut self.boxes[box_number];
+        let slot = lenses.iter().position(|(k, _)| k == key)?;
+        Some(lenses.remove(slot).1)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.boxes[self.box_for(key)].iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn len(&self) -> usize {
+        self.boxes.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boxes.iter().all(Vec::is_empty)
+    }
+
+    // (box number, slot, key, value), box by box and front to back within each box
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, &K, &V)> {
+        self.boxes.iter().enumerate().flat_map(|(box_number, lenses)|
+            lenses.iter().enumerate().map(move |(slot, (k, v))| (box_number, slot, k, v)))
+    }
+
+    pub fn focusing_power(&self) -> u64
+        where V: Copy + Into<u64> {
+        self.iter()
+            .map(|(box_number, slot, _, lens)| (box_number as u64 + 1) * (slot as u64 + 1) * (*lens).into())
+            .sum()
+    }
+}
+
+// same format as the puzzle's worked example, skipping the empty boxes
+impl<K: std::fmt::Display, V: std::fmt::Display> std::fmt::Display for LensHashMap<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (box_number, lenses) in self.boxes.iter().enumerate().filter(|(_, l)| !l.is_empty()) {
+            write!(f, "Box {}:", box_number)?;
+            for (k, v) in lenses {
+                write!(f, " [{} {}]", k, v)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub enum Entry<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+pub struct OccupiedEntry<'a, K, V> {
+    lenses: &'a mut Vec<(K, V)>,
+    slot: usize,
+}
+
+pub struct VacantEntry<'a, K, V> {
+    lenses: &'a mut Vec<(K, V)>,
+    key: K,
+}
+
+impl<'a, K, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => &entry.key,
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+
+        self
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.lenses[self.slot].0
+    }
+
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    pub fn get(&self) -> &V {
+        &self.lenses[self.slot].1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.lenses[self.slot].1
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.lenses[self.slot].1
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.lenses.remove(self.slot).1
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.lenses.push((self.key, value));
+        &mut self.lenses.last_mut().unwrap().1
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Instruction<'a> {
     Insert { label: &'a str, focal_length: u64, },
     Remove { label: &'a str }
 }
 
-impl<'a> From<&'a str> for Instruction<'a> {
-    fn from(value: &'a str) -> Self {
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InstructionError<'a> {
+    // neither an = nor a - at the end
+    NoOperation(&'a str),
+    BadFocalLength(&'a str),
+}
+
+impl<'a> TryFrom<&'a str> for Instruction<'a> {
+    type Error = InstructionError<'a>;
+
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
         if let Some((label, focal_length)) = value.split_once('=') {
-            Self::Insert { label, focal_length: focal_length.parse().unwrap(), }
+            let focal_length = focal_length.parse().map_err(|_| InstructionError::BadFocalLength(value))?;
+            Ok(Self::Insert { label, focal_length, })
+        } else if let Some(label) = value.strip_suffix('-') {
+            Ok(Self::Remove { label })
         } else {
-            assert!(value.ends_with('-'));
-            Self::Remove { label: &value[.. value.len() - 1]}
+            Err(InstructionError::NoOperation(value))
         }
     }
 }
 
-pub fn part_2(input: &str) -> u64 {
-    let mut map = HashMap::new();
-    for step in input.split(',') {
-        match Instruction::from(step) {
+// with trace on, prints what's in the boxes after every step like the puzzle does
+pub fn run_instructions(input: &str, trace: bool) -> Result<LensHashMap<&str, u64>, InstructionError<'_>> {
+    let mut map = LensHashMap::new();
+    for step in input.trim().split(',') {
+        match Instruction::try_from(step)? {
             Instruction::Insert { label, focal_length } => {
-                let box_contents = map.entry(hash(label)).or_insert(Vec::new());
-                if let Some((_, lens)) = box_contents.iter_mut().find(|(l, _)| l == &label) {
-                    *lens = focal_length;
-                } else {
-                    box_contents.push((label, focal_length));
-                }
+                map.insert(label, focal_length);
             },
             Instruction::Remove { label } => {
-                let box_contents = map.entry(hash(label)).or_insert(Vec::new());
-                if let Some(index) = box_contents.iter().position(|(l, _)| l == &label) {
-                    box_contents.remove(index);
-                }
+                map.remove(&label);
             },
         }
+
+        if trace {
+            println!("After \"{}\":\n{}", step, map);
+        }
     }
 
-    map.into_iter().flat_map(
-        |(box_number, lenses)| 
-            lenses.into_iter().enumerate()
-                .map(move |(index, (_, lens))| (box_number + 1) * (index as u64 + 1) * lens)
-    ).sum()
+    Ok(map)
+}
+
+pub fn part_2(input: &str) -> Result<u64, InstructionError<'_>> {
+    Ok(run_instructions(input, false)?.focusing_power())
 }
 
 fn main() {
     let input = include_str!("../input.txt");
+    let trace = std::env::args().any(|arg| arg == "--trace");
     println!("Part 1: {}", part_1(input));
-    println!("Part 2: {}", part_2(input));
+    // part 2 comes straight from the (possibly traced) run
+    println!("Part 2: {:?}", run_instructions(input, trace).map(|map| map.focusing_power()));
 }
 
 #[test]
@@ -63,5 +256,33 @@ pub fn test() {
     assert_eq!(part_1("HASH"), 52);
     let input = r"rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7";
     assert_eq!(part_1(input), 1320);
-    assert_eq!(part_2(input), 145);
+    assert_eq!(part_2(input), Ok(145));
+}
+
+#[test]
+pub fn test_lens_hash_map() {
+    let input = r"rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7";
+    let map = run_instructions(input, false).unwrap();
+    assert_eq!(map.to_string(), "Box 0: [rn 1] [cm 2]\nBox 3: [ot 7] [ab 5] [pc 6]\n");
+    assert_eq!(map.get(&"ot"), Some(&7));
+    assert_eq!(map.get(&"qp"), None);
+    assert_eq!(map.len(), 5);
+
+    // everything in one box, so slot order is all that matters
+    let mut map: LensHashMap<String, u64> = LensHashMap::with_hasher(|_| 0);
+    map.insert("a".to_owned(), 1);
+    map.insert("b".to_owned(), 2);
+    *map.entry("c".to_owned()).or_insert(0) += 3;
+    map.entry("a".to_owned()).and_modify(|lens| *lens += 10);
+    assert_eq!(map.iter().map(|(b, s, k, v)| (b, s, k.as_str(), *v)).collect::<Vec<_>>(), vec![(0, 0, "a", 11), (0, 1, "b", 2), (0, 2, "c", 3)]);
+    assert_eq!(map.focusing_power(), 11 + 2 * 2 + 3 * 3);
+    match map.entry("b".to_owned()) {
+        Entry::Occupied(entry) => assert_eq!(entry.remove(), 2),
+        Entry::Vacant(_) => unreachable!(),
+    }
+    assert_eq!(map.remove(&"a".to_owned()), Some(11));
+    assert_eq!(map.focusing_power(), 3);
+
+    assert_eq!(Instruction::try_from("ab"), Err(InstructionError::NoOperation("ab")));
+    assert_eq!(Instruction::try_from("ab=x"), Err(InstructionError::BadFocalLength("ab=x")));
 }
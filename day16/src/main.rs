@@ -1,4 +1,6 @@
 use std::collections::{HashMap, VecDeque, HashSet};
+use std::fmt::Debug;
+use std::sync::Arc;
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
 pub struct Coord {
@@ -22,6 +24,15 @@ impl Coord {
     pub fn next_right(self) -> Coord {
         Coord { x: self.x + 1, ..self }
     }
+
+    pub fn next(self, direction: Direction) -> Coord {
+        match direction {
+            Direction::Up => self.next_up(),
+            Direction::Down => self.next_down(),
+            Direction::Left => self.next_left(),
+            Direction::Right => self.next_right(),
+        }
+    }
 }
 
 impl From<(i64, i64)> for Coord {
@@ -35,129 +46,502 @@ pub enum Direction {
     Up, Down, Left, Right
 }
 
+impl Direction {
+    pub fn turn_left(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    pub fn turn_right(self) -> Direction {
+        self.turn_left().reverse()
+    }
+
+    pub fn reverse(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Direction::Up | Direction::Down)
+    }
+}
+
+// anything a beam can run into - any state lives in the engine, starting from 0
+pub trait Optic: Debug + Send + Sync {
+    fn symbol(&self) -> char;
+
+    // where a beam arriving in `direction` leaves, while in `state`
+    fn deflect(&self, direction: Direction, state: usize) -> Vec<Direction>;
+
+    fn states(&self) -> usize {
+        1
+    }
+
+    // the state the tile is left in once a beam has passed through
+    fn next_state(&self, state: usize, _direction: Direction) -> usize {
+        state
+    }
+}
+
+// '/' and '\'
+#[derive(Debug, Clone, Copy)]
+pub struct Mirror {
+    forward: bool,
+}
+
+impl Optic for Mirror {
+    fn symbol(&self) -> char {
+        if self.forward { '/' } else { '\\' }
+    }
+
+    fn deflect(&self, direction: Direction, _state: usize) -> Vec<Direction> {
+        // '/' turns a beam going right upwards, '\' turns it downwards
+        let turned = if direction.is_vertical() == self.forward {
+            direction.turn_right()
+        } else {
+            direction.turn_left()
+        };
+        vec![turned]
+    }
+}
+
+// '|' and '-'
+#[derive(Debug, Clone, Copy)]
+pub struct Splitter {
+    vertical: bool,
+}
+
+impl Optic for Splitter {
+    fn symbol(&self) -> char {
+        if self.vertical { '|' } else { '-' }
+    }
+
+    fn deflect(&self, direction: Direction, _state: usize) -> Vec<Direction> {
+        if direction.is_vertical() == self.vertical {
+            vec![direction]
+        } else {
+            vec![direction.turn_left(), direction.turn_right()]
+        }
+    }
+}
+
+// '#' swallows any beam that reaches it
+#[derive(Debug, Clone, Copy)]
+pub struct Absorber;
+
+impl Optic for Absorber {
+    fn symbol(&self) -> char {
+        '#'
+    }
+
+    fn deflect(&self, _direction: Direction, _state: usize) -> Vec<Direction> {
+        vec![]
+    }
+}
+
+// '^', 'v', '<' and '>' only let through beams heading the way they point
+#[derive(Debug, Clone, Copy)]
+pub struct OneWayGate {
+    direction: Direction,
+}
+
+impl Optic for OneWayGate {
+    fn symbol(&self) -> char {
+        match self.direction {
+            Direction::Up => '^',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+            Direction::Right => '>',
+        }
+    }
+
+    fn deflect(&self, direction: Direction, _state: usize) -> Vec<Direction> {
+        if direction == self.direction { vec![direction] } else { vec![] }
+    }
+}
+
+// '*' sends a beam on straight ahead as well as out to both sides
+#[derive(Debug, Clone, Copy)]
+pub struct Prism;
+
+impl Optic for Prism {
+    fn symbol(&self) -> char {
+        '*'
+    }
+
+    fn deflect(&self, direction: Direction, _state: usize) -> Vec<Direction> {
+        vec![direction.turn_left(), direction, direction.turn_right()]
+    }
+}
+
+// '+' starts out as a '|' splitter and flips between '|' and '-' every
+// time a beam goes through it - so what it does depends on the (breadth-first) order beams reach it
+#[derive(Debug, Clone, Copy)]
+pub struct TogglingSplitter;
+
+impl Optic for TogglingSplitter {
+    fn symbol(&self) -> char {
+        '+'
+    }
+
+    fn deflect(&self, direction: Direction, state: usize) -> Vec<Direction> {
+        Splitter { vertical: state == 0 }.deflect(direction, 0)
+    }
+
+    fn states(&self) -> usize {
+        2
+    }
+
+    fn next_state(&self, state: usize, _direction: Direction) -> usize {
+        1 - state
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownTile {
+    pub symbol: char,
+    pub coord: Coord,
+}
+
+// which optic each character in the layout stands for
+#[derive(Debug, Clone, Default)]
+pub struct OpticRegistry {
+    optics: HashMap<char, Arc<dyn Optic>>,
+}
+
+impl OpticRegistry {
+    pub fn new() -> OpticRegistry {
+        OpticRegistry::default()
+    }
+
+    // just the mirrors and splitters from the puzzle
+    pub fn standard() -> OpticRegistry {
+        let mut registry = OpticRegistry::new();
+        registry
+            .register(Mirror { forward: true })
+            .register(Mirror { forward: false })
+            .register(Splitter { vertical: true })
+            .register(Splitter { vertical: false });
+        registry
+    }
+
+    pub fn extended() -> OpticRegistry {
+        let mut registry = OpticRegistry::standard();
+        registry.register(Absorber).register(Prism).register(TogglingSplitter);
+        for direction in [Direction::Up, Direction::Down, Direction::Left, Direction::Right] {
+            registry.register(OneWayGate { direction });
+        }
+        registry
+    }
+
+    // replaces anything already registered under the same symbol
+    pub fn register(&mut self, optic: impl Optic + 'static) -> &mut OpticRegistry {
+        self.optics.insert(optic.symbol(), Arc::new(optic));
+        self
+    }
+
+    pub fn get(&self, symbol: char) -> Option<&Arc<dyn Optic>> {
+        self.optics.get(&symbol)
+    }
+
+    pub fn parse(&self, input: &str) -> Result<Cavern, UnknownTile> {
+        let mut max_size = Coord::from((-1, -1));
+        let mut tiles = HashMap::new();
+        for (y, line) in input.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                let coord: Coord = (x as i64, y as i64).into();
+                if c != '.' {
+                    let optic = self.get(c).ok_or(UnknownTile { symbol: c, coord })?;
+                    tiles.insert(coord, Arc::clone(optic));
+                }
+
+                max_size = coord;
+            }
+        }
+
+        Ok(Cavern { tiles, max_size })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Cavern {
-    tiles: HashMap<Coord, char>,
+    tiles: HashMap<Coord, Arc<dyn Optic>>,
     max_size: Coord,
 }
 
+impl Cavern {
+    pub fn contains(&self, coord: Coord) -> bool {
+        coord.x >= 0 && coord.y >= 0 && coord.x <= self.max_size.x && coord.y <= self.max_size.y
+    }
+
+    pub fn tile(&self, coord: Coord) -> Option<&dyn Optic> {
+        self.tiles.get(&coord).map(|optic| optic.as_ref())
+    }
+
+    pub fn is_stateless(&self) -> bool {
+        self.tiles.values().all(|optic| optic.states() == 1)
+    }
+}
+
 pub fn parse_input(input: &str) -> Cavern {
-    let mut max_size = None;
-    let mut tiles = HashMap::new();
-    for (y, line) in input.lines().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            let current_coord: Coord = (x as i64, y as i64).into();
-            if c != '.' {
-                tiles.insert(current_coord, c);
-            }
+    OpticRegistry::standard().parse(input).unwrap()
+}
 
-            max_size = Some(current_coord);
-        }
+// every beam segment in the order the engine followed it
+#[derive(Debug, Clone, Default)]
+pub struct BeamPath {
+    steps: Vec<(Coord, Direction)>,
+}
+
+impl BeamPath {
+    pub fn steps(&self) -> &[(Coord, Direction)] {
+        &self.steps
     }
 
-    let max_size = max_size.unwrap();
-    Cavern { tiles, max_size }
+    // the cells a beam passed through while heading `direction`, in the order
+    // they were first reached
+    pub fn cells_moving(&self, direction: Direction) -> Vec<Coord> {
+        let mut seen = HashSet::new();
+        self.steps.iter()
+            .filter(|(_, d)| *d == direction)
+            .map(|(c, _)| *c)
+            .filter(|c| seen.insert(*c))
+            .collect()
+    }
+
+    pub fn energised(&self) -> usize {
+        self.steps.iter().map(|(c, _)| c).collect::<HashSet<_>>().len()
+    }
 }
 
-pub fn get_energised_cells(cavern: &Cavern, start_state: (Coord, Direction)) -> usize {
-    // which coords have had passing through them in which way
-    let mut seen_states = HashMap::new();
+pub fn trace_beam(cavern: &Cavern, start_state: (Coord, Direction)) -> BeamPath {
+    // which way beams have passed through each coord, and what state the
+    // tile there was in at the time
+    let mut seen_states = HashSet::new();
+    let mut tile_states: HashMap<Coord, usize> = HashMap::new();
+    let mut path = BeamPath::default();
     let mut cells_to_process = VecDeque::new();
     cells_to_process.push_back(start_state);
 
     while let Some((coord, direction)) = cells_to_process.pop_front() {
-        if seen_states.insert((coord, direction), ()).is_some() {
+        if !cavern.contains(coord) {
             continue;
         }
 
-        // ignore anything out of bounds as well
-        if coord.x < 0 || coord.y < 0
-            || coord.x > cavern.max_size.x || coord.y > cavern.max_size.y {
-                continue;
-            }
+        let state = tile_states.get(&coord).copied().unwrap_or(0);
+        if !seen_states.insert((coord, direction, state)) {
+            continue;
+        }
+        path.steps.push((coord, direction));
 
-        match (cavern.tiles.get(&coord), direction) {
-            (None | Some('|'), Direction::Up) => {
-                cells_to_process.push_back((coord.next_up(), Direction::Up));
-            },
-            (None | Some('-'), Direction::Right) => {
-                cells_to_process.push_back((coord.next_right(), Direction::Right));
-            },
-            (None | Some('-'), Direction::Left) => {
-                cells_to_process.push_back((coord.next_left(), Direction::Left));
-            },
-            (None | Some('|'), Direction::Down) => {
-                cells_to_process.push_back((coord.next_down(), Direction::Down));
-            },
-            (Some('\\'), Direction::Left) | (Some('/'), Direction::Right) => {
-                cells_to_process.push_back((coord.next_up(), Direction::Up));
-            },
-            (Some('\\'), Direction::Right) | (Some('/'), Direction::Left) => {
-                cells_to_process.push_back((coord.next_down(), Direction::Down));
-            },
-            (Some('\\'), Direction::Up) | (Some('/'), Direction::Down) => {
-                cells_to_process.push_back((coord.next_left(), Direction::Left));
-            },
-            (Some('\\'), Direction::Down) | (Some('/'), Direction::Up) => {
-                cells_to_process.push_back((coord.next_right(), Direction::Right));
-            },
-            (Some('|'), Direction::Left | Direction::Right) => {
-                cells_to_process.push_back((coord.next_up(), Direction::Up));
-                cells_to_process.push_back((coord.next_down(), Direction::Down));
-            },
-            (Some('-'), Direction::Up | Direction::Down) => {
-                cells_to_process.push_back((coord.next_left(), Direction::Left));
-                cells_to_process.push_back((coord.next_right(), Direction::Right));
-            },
-            _ => unreachable!(),
-        }
-    }
-
-    let energised_coords = seen_states.keys().map(|(c, _)| c).collect::<HashSet<_>>();
-
-    let mut energised_cells = 0_usize;
-    for x in 0 ..= cavern.max_size.x {
-        for y in 0 ..= cavern.max_size.y {
-            let c = Coord::from((x, y));
-            if energised_coords.contains(&c) {
-                energised_cells += 1;
+        let next_directions = match cavern.tile(coord) {
+            None => vec![direction],
+            Some(optic) => {
+                if optic.states() > 1 {
+                    tile_states.insert(coord, optic.next_state(state, direction));
+                }
+                optic.deflect(direction, state)
             }
+        };
+        for next_direction in next_directions {
+            cells_to_process.push_back((coord.next(next_direction), next_direction));
         }
     }
 
-    energised_cells
+    path
+}
+
+pub fn get_energised_cells(cavern: &Cavern, start_state: (Coord, Direction)) -> usize {
+    trace_beam(cavern, start_state).energised()
 }
 
 pub fn part_1(cavern: &Cavern) -> usize {
     get_energised_cells(cavern, ((0, 0).into(), Direction::Right))
 }
 
+// every (start, direction) a beam can enter the cavern from
+pub fn edge_entries(cavern: &Cavern) -> Vec<(Coord, Direction)> {
+    let max = cavern.max_size;
+    (0 ..= max.x).map(|x| ((x, 0).into(), Direction::Down))
+        .chain((0 ..= max.x).map(|x| ((x, max.y).into(), Direction::Up)))
+        .chain((0 ..= max.y).map(|y| ((0, y).into(), Direction::Right)))
+        .chain((0 ..= max.y).map(|y| ((max.x, y).into(), Direction::Left)))
+        .collect()
+}
+
+// beam segments condensed into their strongly connected components, so every entry is answered
+// in one pass (only valid when no tile has any state)
+pub struct BeamGraph {
+    nodes: HashMap<(Coord, Direction), usize>,
+    energised: Vec<usize>,
+}
+
+impl BeamGraph {
+    pub fn new(cavern: &Cavern, entries: &[(Coord, Direction)]) -> Option<BeamGraph> {
+        if !cavern.is_stateless() {
+            return None;
+        }
+
+        let width = cavern.max_size.x + 1;
+        let in_bounds = |c: Coord| cavern.contains(c);
+        let cell_index = |c: Coord| (c.y * width + c.x) as usize;
+
+        let mut nodes = HashMap::new();
+        let mut states = Vec::new();
+        for &entry in entries.iter().filter(|(c, _)| in_bounds(*c)) {
+            nodes.entry(entry).or_insert_with(|| {
+                states.push(entry);
+                states.len() - 1
+            });
+        }
+
+        let mut own_cells: Vec<Vec<usize>> = Vec::new();
+        let mut edges: Vec<Vec<usize>> = Vec::new();
+        let mut next_node = 0;
+        while next_node < states.len() {
+            let (coord, direction) = states[next_node];
+            let mut cells = vec![cell_index(coord)];
+            let mut targets = Vec::new();
+            let next_directions = match cavern.tile(coord) {
+                None => vec![direction],
+                Some(optic) => optic.deflect(direction, 0),
+            };
+            for next_direction in next_directions {
+                let mut c = coord.next(next_direction);
+                while in_bounds(c) {
+                    if cavern.tile(c).is_some() {
+                        let state = (c, next_direction);
+                        let id = *nodes.entry(state).or_insert_with(|| {
+                            states.push(state);
+                            states.len() - 1
+                        });
+                        targets.push(id);
+                        break;
+                    }
+                    cells.push(cell_index(c));
+                    c = c.next(next_direction);
+                }
+            }
+            own_cells.push(cells);
+            edges.push(targets);
+            next_node += 1;
+        }
+
+        let (components, component_of) = strongly_connected_components(&edges);
+
+        // components come out of tarjan sinks first, so successors are always
+        // finished before anything that can reach them
+        let words = ((width * (cavern.max_size.y + 1)) as usize).div_ceil(64);
+        let mut cell_sets: Vec<Vec<u64>> = Vec::with_capacity(components.len());
+        let mut component_energised = Vec::with_capacity(components.len());
+        for (component, members) in components.iter().enumerate() {
+            let mut set = vec![0_u64; words];
+            for &node in members {
+                for &cell in &own_cells[node] {
+                    set[cell / 64] |= 1 << (cell % 64);
+                }
+                for &target in &edges[node] {
+                    let below = component_of[target];
+                    if below != component {
+                        for (word, other) in set.iter_mut().zip(&cell_sets[below]) {
+                            *word |= other;
+                        }
+                    }
+                }
+            }
+            component_energised.push(set.iter().map(|w| w.count_ones() as usize).sum());
+            cell_sets.push(set);
+        }
+
+        let energised = component_of.iter().map(|&c| component_energised[c]).collect();
+        Some(BeamGraph { nodes, energised })
+    }
+
+    pub fn energised_cells(&self, start: (Coord, Direction)) -> Option<usize> {
+        self.nodes.get(&start).map(|&node| self.energised[node])
+    }
+}
+
+// iterative tarjan, returning components in reverse topological order along
+// with the component each node belongs to
+fn strongly_connected_components(edges: &[Vec<usize>]) -> (Vec<Vec<usize>>, Vec<usize>) {
+    let n = edges.len();
+    let mut index = vec![usize::MAX; n];
+    let mut low_link = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+    let mut component_of = vec![0; n];
+    let mut next_index = 0;
+
+    for root in 0..n {
+        if index[root] != usize::MAX {
+            continue;
+        }
+        let mut call_stack = vec![(root, 0)];
+        index[root] = next_index;
+        low_link[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&mut (node, ref mut next_edge)) = call_stack.last_mut() {
+            if let Some(&target) = edges[node].get(*next_edge) {
+                *next_edge += 1;
+                if index[target] == usize::MAX {
+                    index[target] = next_index;
+                    low_link[target] = next_index;
+                    next_index += 1;
+                    stack.push(target);
+                    on_stack[target] = true;
+                    call_stack.push((target, 0));
+                } else if on_stack[target] {
+                    low_link[node] = low_link[node].min(index[target]);
+                }
+                continue;
+            }
+
+            call_stack.pop();
+            if let Some(&(parent, _)) = call_stack.last() {
+                low_link[parent] = low_link[parent].min(low_link[node]);
+            }
+            if low_link[node] == index[node] {
+                let mut members = Vec::new();
+                loop {
+                    let member = stack.pop().unwrap();
+                    on_stack[member] = false;
+                    component_of[member] = components.len();
+                    members.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                components.push(members);
+            }
+        }
+    }
+
+    (components, component_of)
+}
+
 pub fn part_2(cavern: &Cavern) -> usize {
-    let best_result_from_top =
-        (0 ..= cavern.max_size.x)
-            .map(|x| ((x, 0).into(), Direction::Down))
-            .map(|s| get_energised_cells(cavern, s))
-            .max().unwrap();
-    let best_result_from_bottom =
-        (0 ..= cavern.max_size.x)
-            .map(|x| ((x, cavern.max_size.y).into(), Direction::Up))
-            .map(|s| get_energised_cells(cavern, s))
-            .max().unwrap();
-    let best_result_from_left =
-        (0 ..= cavern.max_size.y)
-            .map(|y| ((0, y).into(), Direction::Right))
-            .map(|s| get_energised_cells(cavern, s))
-            .max().unwrap();
-    let best_result_from_right =
-        (0 ..= cavern.max_size.y)
-            .map(|y| ((cavern.max_size.x, y).into(), Direction::Left))
-            .map(|s| get_energised_cells(cavern, s))
-            .max().unwrap();
-
-    best_result_from_top
-        .max(best_result_from_bottom)
-        .max(best_result_from_left)
-        .max(best_result_from_right)
+    let entries = edge_entries(cavern);
+    match BeamGraph::new(cavern, &entries) {
+        Some(graph) => entries.iter()
+            .filter_map(|&entry| graph.energised_cells(entry))
+            .max().unwrap(),
+        None => entries.iter()
+            .map(|&entry| get_energised_cells(cavern, entry))
+            .max().unwrap(),
+    }
 }
 
 pub fn main() {
@@ -183,4 +567,53 @@ pub fn test() {
     let cavern = parse_input(input);
     assert_eq!(part_1(&cavern), 46);
     assert_eq!(part_2(&cavern), 51);
-}
\ No newline at end of file
+}
+
+#[test]
+pub fn test_beam_graph() {
+    let input = r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....";
+
+    let cavern = parse_input(input);
+    let entries = edge_entries(&cavern);
+    let graph = BeamGraph::new(&cavern, &entries).unwrap();
+    for entry in entries {
+        assert_eq!(graph.energised_cells(entry), Some(get_energised_cells(&cavern, entry)));
+    }
+}
+
+#[test]
+pub fn test_extended_optics() {
+    let registry = OpticRegistry::extended();
+
+    let absorbed = registry.parse("..#..").unwrap();
+    assert_eq!(get_energised_cells(&absorbed, ((0, 0).into(), Direction::Right)), 3);
+
+    let gated = registry.parse(".<..").unwrap();
+    assert_eq!(get_energised_cells(&gated, ((0, 0).into(), Direction::Right)), 2);
+    assert_eq!(get_energised_cells(&gated, ((3, 0).into(), Direction::Left)), 4);
+
+    let prism = registry.parse("...\n.*.\n...").unwrap();
+    assert_eq!(get_energised_cells(&prism, ((1, 0).into(), Direction::Down)), 5);
+
+    // the beam coming back round finds the splitter flipped to '-' and
+    // carries straight on out of the left edge
+    let toggling = registry.parse(".....\n.+..\\\n.....\n.\\../").unwrap();
+    assert!(BeamGraph::new(&toggling, &edge_entries(&toggling)).is_none());
+    let path = trace_beam(&toggling, ((0, 1).into(), Direction::Right));
+    assert_eq!(path.energised(), 12);
+    assert_eq!(
+        path.cells_moving(Direction::Left),
+        vec![(3, 1).into(), (2, 1).into(), (1, 1).into(), (0, 1).into()]);
+
+    assert_eq!(registry.parse("..@").unwrap_err(), UnknownTile { symbol: '@', coord: (2, 0).into() });
+    assert!(OpticRegistry::standard().parse("*").is_err());
+}
@@ -1,5 +1,4 @@
-use std::{collections::{HashMap, HashSet, BTreeSet}, ops::Add};
-use itertools::Itertools;
+use std::{cmp::Reverse, collections::{BTreeMap, BinaryHeap, HashMap, HashSet}, fmt::Write, ops::Add};
 
 #[derive(PartialEq, Eq, Copy, Clone, Hash, Debug, Ord, PartialOrd)]
 pub struct Coord {
@@ -54,6 +53,24 @@ impl Direction {
         }
     }
 
+    pub fn letter(self) -> char {
+        match self {
+            Self::Up => 'U',
+            Self::Down => 'D',
+            Self::Left => 'L',
+            Self::Right => 'R',
+        }
+    }
+
+    pub fn arrow(self) -> char {
+        match self {
+            Self::Up => '^',
+            Self::Down => 'v',
+            Self::Left => '<',
+            Self::Right => '>',
+        }
+    }
+
     pub fn possible_options(self) -> [Self; 2] {
         match self {
             Self::Up | Self::Down  => [Self::Left, Self::Right],
@@ -76,6 +93,103 @@ impl Heading {
     }
 }
 
+// a straight run of `run` blocks in `direction`, starting from (but not
+// including) `start`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RouteSegment {
+    pub start: Coord,
+    pub direction: Direction,
+    pub run: u64,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Route {
+    pub heat_loss: u64,
+    pub segments: Vec<RouteSegment>,
+}
+
+impl Route {
+    // every block entered along the way, with the direction it was entered in
+    pub fn steps(&self) -> Vec<(Coord, Direction)> {
+        let mut steps = vec![];
+        for segment in &self.segments {
+            let mut coord = segment.start;
+            for _ in 0..segment.run {
+                coord = coord.next(segment.direction);
+                steps.push((coord, segment.direction));
+            }
+        }
+        steps
+    }
+
+    // the route as a list of moves, eg "R2,D1,R3"
+    pub fn moves(&self) -> String {
+        self.segments.iter()
+            .map(|s| format!("{}{}", s.direction.letter(), s.run))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+// the best way found to each (block, heading), and where it came from
+type Predecessors = HashMap<(Coord, Heading), ((Coord, Heading), RouteSegment)>;
+
+// the cheapest way from `start` to each of `goals`, moving `min_run` to `max_run` blocks at a time
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub start: Coord,
+    pub goals: HashSet<Coord>,
+    pub min_run: u64,
+    pub max_run: u64,
+    pub use_heuristic: bool,
+}
+
+impl Search {
+    pub fn new(start: Coord, goals: impl IntoIterator<Item = Coord>, min_run: u64, max_run: u64) -> Search {
+        let goals = goals.into_iter().collect();
+        Search { start, goals, min_run, max_run, use_heuristic: false }
+    }
+
+    // guide the search with A*: no block costs less than the cheapest one in
+    // the city, so that times the distance to the nearest goal never
+    // overestimates
+    pub fn with_heuristic(self) -> Search {
+        Search { use_heuristic: true, ..self }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    best_routes: HashMap<(Coord, Heading), u64>,
+    predecessors: Predecessors,
+    // the cheapest (block, heading) found for each goal reached
+    goals: HashMap<Coord, (Coord, Heading)>,
+}
+
+impl SearchResult {
+    // the settled heat loss for every (block, heading) the search finished with
+    pub fn best_routes(&self) -> &HashMap<(Coord, Heading), u64> {
+        &self.best_routes
+    }
+
+    pub fn heat_loss(&self, goal: Coord) -> Option<u64> {
+        self.goals.get(&goal).map(|state| self.best_routes[state])
+    }
+
+    pub fn route(&self, goal: Coord) -> Option<Route> {
+        let mut current = *self.goals.get(&goal)?;
+        let heat_loss = self.best_routes[&current];
+
+        let mut segments = vec![];
+        while let Some((previous, segment)) = self.predecessors.get(&current) {
+            segments.push(*segment);
+            current = *previous;
+        }
+        segments.reverse();
+        Some(Route { heat_loss, segments })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct City {
     block_weights: HashMap<Coord, u64>,
@@ -88,98 +202,262 @@ impl City {
     }
 
     pub fn calculate_best_weights(&self, min_run: u64, max_run: u64) -> HashMap<(Coord, Heading), u64> {
-        let all_headings = [Heading::Horizontal, Heading::Vertical];
+        self.search(&Search::new((0, 0).into(), [self.max_size], min_run, max_run)).best_routes
+    }
+
+    // the cheapest route from the top left to the bottom right, if there is one
+    pub fn best_route(&self, min_run: u64, max_run: u64) -> Option<Route> {
+        self.search(&Search::new((0, 0).into(), [self.max_size], min_run, max_run))
+            .route(self.max_size)
+    }
+
+    // the city's heat loss digits, with the blocks on the route replaced by
+    // arrows showing which way the crucible was moving
+    pub fn render_route(&self, route: &Route) -> String {
+        let arrows = route.steps().into_iter().collect::<HashMap<_, _>>();
+        let mut output = String::new();
+        for y in 0 ..= self.max_size.y {
+            for x in 0 ..= self.max_size.x {
+                let coord = Coord::from((x, y));
+                match arrows.get(&coord) {
+                    Some(direction) => output.push(direction.arrow()),
+                    None => write!(output, "{}", self.block_weights[&coord]).unwrap(),
+                }
+            }
+            output.push('\n');
+        }
+        output
+    }
 
+    // dijkstra (or A*) over (block, heading), ties being settled by the
+    // ordering of (estimate, score, coord, heading) and the first route found
+    // to a block being kept, so the same grid always gives the same route
+    pub fn search(&self, search: &Search) -> SearchResult {
         // to cope with the "at most three in a line", rather than calculating the best route
         // to a block as normal, we'll calculate "best route to a block that enters it heading in
         // direction D", for all directions that make sense for the block.
         // and consider all blocks in a line of min_run..=max_run to be equally 'neighbours'
-
-        // keep track of which blocks we've finished with, and what our 'best route' numbers are
-        let mut visited_blocks = HashSet::new();
-        let mut best_routes = HashMap::new();
-        // keep track of unfinished stuff in two ways: once in a map for easy lookup of current best cost,
-        // and once in a set for easy retrieval of "smallest cost block" to handle next
-        let mut unvisited_blocks = HashMap::new();
-        let mut unvisited_blocks_sorted = BTreeSet::new();
-
-        // fill in the unvisited blocks (do we actually need to do this?):
-        for coord in self.block_weights.keys() {
-            for heading in all_headings {
-                unvisited_blocks.insert((*coord, heading), None);
+        let cheapest_block = self.block_weights.values().min().copied().unwrap_or(0);
+        let estimate = |coord: Coord| -> u64 {
+            if !search.use_heuristic {
+                return 0;
+            }
+            search.goals.iter()
+                .map(|goal| goal.x.abs_diff(coord.x) + goal.y.abs_diff(coord.y))
+                .min()
+                .unwrap_or(0) * cheapest_block
+        };
+
+        let mut result = SearchResult {
+            best_routes: HashMap::new(),
+            predecessors: Predecessors::new(),
+            goals: HashMap::new(),
+        };
+        // the best score seen so far for everything not yet finished with. the
+        // heap can hold stale entries for a block that's since been improved;
+        // those get skipped when they come out
+        let mut tentative = HashMap::new();
+        let mut unvisited_blocks = BinaryHeap::new();
+
+        // the start point can be left with either heading for nothing
+        if self.in_bounds(search.start) {
+            for heading in [Heading::Horizontal, Heading::Vertical] {
+                tentative.insert((search.start, heading), 0);
+                unvisited_blocks.push(Reverse((estimate(search.start), 0, search.start, heading)));
             }
         }
 
-        // replace the start point so we know we can get there (with either heading) in 0
-        unvisited_blocks.insert((Coord::from((0, 0)), Heading::Horizontal), Some(0));
-        unvisited_blocks.insert((Coord::from((0, 0)), Heading::Vertical), Some(0));
-        unvisited_blocks_sorted.insert((0, Coord::from((0, 0)), Heading::Horizontal));
-        unvisited_blocks_sorted.insert((0, Coord::from((0, 0)), Heading::Vertical));
+        while let Some(Reverse((_, score, coord, current_heading))) = unvisited_blocks.pop() {
+            if result.best_routes.contains_key(&(coord, current_heading))
+                || tentative[&(coord, current_heading)] < score {
+                continue;
+            }
+            result.best_routes.insert((coord, current_heading), score);
 
-        while let Some((score, coord, current_heading)) = unvisited_blocks_sorted.pop_first()
-            {
-                // bail out early condition - we've found the shortest way of getting there with some heading
-                if coord == self.max_size {
-                    best_routes.insert((coord, current_heading), score);
+            // the first time a goal comes out is the best way there, whatever the heading
+            if search.goals.contains(&coord) {
+                result.goals.entry(coord).or_insert((coord, current_heading));
+                if result.goals.len() == search.goals.len() {
                     break;
                 }
+            }
 
-                if visited_blocks.contains(&(coord, current_heading)) {
-                    unreachable!(); // just in case?
-                    // continue;
-                }
+            // find the neighbours: this is all blocks within max_run of our current cell,
+            // except in the direction we came from
+            // (by assumption, we've exhausted that heading for this route)
+            for direction in current_heading.possible_directions() {
+                let mut accumulated_loss_this_heading = 0;
+                let mut destination = coord;
+                for run in 1 ..= search.max_run {
+                    destination = destination.next(direction);
+                    let Some(loss) = self.block_weights.get(&destination) else {
+                        // shouldn't go this way anyway
+                        break;
+                    };
+                    accumulated_loss_this_heading += loss;
+                    if run < search.min_run {
+                        // not allowed to stop yet though
+                        continue;
+                    }
 
-                // find the neighbours: this is all blocks within three of our current cell,
-                // except in the direction we came from
-                // (by assumption, we've exhausted that heading for this route)
-                for direction in current_heading.possible_directions() {
-                    let mut accumulated_loss_this_heading = 0;
-                    let mut destination = coord;
-                    for run in 1 ..= max_run {
-                        destination = destination.next(direction);
-                        match self.block_weights.get(&destination) {
-                            Some(loss) => {
-                                accumulated_loss_this_heading += loss;
-                                let total_loss_here = score + accumulated_loss_this_heading;
-                                if run < min_run {
-                                    // not allowed to stop yet though
-                                    continue;
-                                }
-                                let new_heading = direction.heading();
-                                let current = unvisited_blocks.get(&(destination, new_heading));
-                                match current {
-                                    // we've found a better route
-                                    Some(&Some(cost)) if cost > total_loss_here =>
-                                        {
-                                            unvisited_blocks.insert((destination, new_heading), Some(total_loss_here));
-                                            unvisited_blocks_sorted.remove(&(cost, destination, new_heading));
-                                            unvisited_blocks_sorted.insert((total_loss_here, destination, new_heading));
-
-                                        },
-                                    Some(None) => 
-                                        {
-                                            unvisited_blocks.insert((destination, new_heading), Some(total_loss_here));
-                                            unvisited_blocks_sorted.insert((total_loss_here, destination, new_heading));
-                                        },
-                                    _ => { },
-                                }
-                            },
-                            None => {
-                                // shouldn't go this way anyway
-                                break;
-                            }
-                        }
+                    let total_loss_here = score + accumulated_loss_this_heading;
+                    let next = (destination, direction.heading());
+                    if tentative.get(&next).is_some_and(|&best| best <= total_loss_here) {
+                        continue;
                     }
+                    tentative.insert(next, total_loss_here);
+                    result.predecessors.insert(
+                        next, ((coord, current_heading), RouteSegment { start: coord, direction, run }));
+                    unvisited_blocks.push(Reverse(
+                        (total_loss_here + estimate(destination), total_loss_here, next.0, next.1)));
                 }
+            }
+        }
+
+        result
+    }
+}
+
+// heat loss, turns (or segments, while searching) and blocks travelled
+pub type Costs = (u64, u64, u64);
 
-                // mark this as visited and carry on
-                visited_blocks.insert((coord, current_heading));
-                // and also remove it from the unvisited list
-                let best_score = unvisited_blocks.remove(&(coord, current_heading)).unwrap();
-                best_routes.insert((coord, current_heading), best_score.unwrap());
+// a route that nothing beats on heat loss, turns and distance all at once
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParetoRoute {
+    pub heat_loss: u64,
+    pub turns: u64,
+    pub distance: u64,
+    pub route: Route,
+}
+
+impl ParetoRoute {
+    pub fn costs(&self) -> Costs {
+        (self.heat_loss, self.turns, self.distance)
+    }
+
+    // at least as good as `other` on every count
+    pub fn dominates(&self, other: &ParetoRoute) -> bool {
+        self.heat_loss <= other.heat_loss && self.turns <= other.turns && self.distance <= other.distance
+    }
+}
+
+// a partial route in the multi-objective search, pointing back at the label
+// it was extended from
+#[derive(Copy, Clone, Debug)]
+struct Label {
+    state: (Coord, Heading),
+    previous: Option<(usize, RouteSegment)>,
+}
+
+// the (turns, distance) pairs kept for one state - labels come out in heat loss order,
+// so those are the only two left to compare
+#[derive(Debug, Clone, Default)]
+struct Staircase(BTreeMap<u64, u64>);
+
+impl Staircase {
+    fn dominates(&self, (_, turns, distance): Costs) -> bool {
+        self.0.range(..= turns).next_back().is_some_and(|(_, &shortest)| shortest <= distance)
+    }
+
+    fn insert(&mut self, (_, turns, distance): Costs) {
+        let beaten = self.0.range(turns ..)
+            .take_while(|(_, &d)| d >= distance)
+            .map(|(&t, _)| t)
+            .collect::<Vec<_>>();
+        for t in beaten {
+            self.0.remove(&t);
+        }
+        self.0.insert(turns, distance);
+    }
+}
+
+impl City {
+    // every unbeatable route from `start` to `goal`, cheapest first (labels come off the heap
+    // in order of their lower bound, so nothing later can beat one already kept)
+    pub fn pareto_routes(&self, start: Coord, goal: Coord, min_run: u64, max_run: u64) -> Vec<ParetoRoute> {
+        // the least a label could cost by the time it reaches the goal, where
+        // it'll be charged for turns rather than segments, so that standing
+        // still doesn't get beaten by both of its headings
+        let cheapest_block = self.block_weights.values().min().copied().unwrap_or(0);
+        let lower_bound = |coord: Coord, costs: Costs| -> Costs {
+            let remaining = goal.x.abs_diff(coord.x) + goal.y.abs_diff(coord.y);
+            (
+                costs.0 + remaining * cheapest_block,
+                (costs.1 + remaining.div_ceil(max_run.max(1))).saturating_sub(1),
+                costs.2 + remaining,
+            )
+        };
+        let mut labels = vec![];
+        let mut kept: HashMap<(Coord, Heading), Staircase> = HashMap::new();
+        let mut at_goal = vec![];
+        let mut goal_staircase = Staircase::default();
+        let mut unvisited_labels: BinaryHeap<Reverse<(Costs, Costs, usize)>> = BinaryHeap::new();
+
+        if self.in_bounds(start) {
+            for heading in [Heading::Horizontal, Heading::Vertical] {
+                labels.push(Label { state: (start, heading), previous: None });
+                unvisited_labels.push(Reverse((lower_bound(start, (0, 0, 0)), (0, 0, 0), labels.len() - 1)));
             }
+        }
 
-        best_routes
+        while let Some(Reverse((goal_costs, costs, index))) = unvisited_labels.pop() {
+            let (coord, current_heading) = labels[index].state;
+            if goal_staircase.dominates(goal_costs) {
+                continue;
+            }
+            let kept_here = kept.entry(labels[index].state).or_default();
+            if kept_here.dominates(costs) {
+                continue;
+            }
+            kept_here.insert(costs);
+
+            if coord == goal {
+                goal_staircase.insert(goal_costs);
+                at_goal.push((goal_costs, index));
+                continue;
+            }
+
+            for direction in current_heading.possible_directions() {
+                let mut accumulated_loss_this_heading = 0;
+                let mut destination = coord;
+                for run in 1 ..= max_run {
+                    destination = destination.next(direction);
+                    let Some(loss) = self.block_weights.get(&destination) else {
+                        break;
+                    };
+                    accumulated_loss_this_heading += loss;
+                    if run < min_run {
+                        continue;
+                    }
+
+                    let next_costs = (costs.0 + accumulated_loss_this_heading, costs.1 + 1, costs.2 + run);
+                    let next_state = (destination, direction.heading());
+                    let next_bound = lower_bound(destination, next_costs);
+                    if goal_staircase.dominates(next_bound)
+                        || kept.get(&next_state).is_some_and(|k| k.dominates(next_costs)) {
+                        continue;
+                    }
+                    let segment = RouteSegment { start: coord, direction, run };
+                    labels.push(Label {
+                        state: next_state,
+                        previous: Some((index, segment)),
+                    });
+                    unvisited_labels.push(Reverse((next_bound, next_costs, labels.len() - 1)));
+                }
+            }
+        }
+
+        at_goal.into_iter()
+            .map(|((heat_loss, turns, distance), mut index)| {
+                let mut segments = vec![];
+                while let Some((previous, segment)) = labels[index].previous {
+                    segments.push(segment);
+                    index = previous;
+                }
+                segments.reverse();
+                ParetoRoute { heat_loss, turns, distance, route: Route { heat_loss, segments } }
+            })
+            .collect()
     }
 }
 
@@ -245,6 +523,150 @@ pub fn test_smaller() {
 32";
 
     let city = parse_input(input);
-    // dbg!(city.calculate_best_weights());
-    // dbg!(part_1(&city));
-}
\ No newline at end of file
+    assert_eq!(part_1(&city), 5);
+    let route = city.best_route(1, 3).unwrap();
+    assert_eq!(route.moves(), "D1,R1");
+    assert_eq!(city.render_route(&route), "24\nv>\n");
+}
+
+#[test]
+pub fn test_best_route() {
+    let input = r"2413432311323
+3215453535623
+3255245654254
+3446585845452
+4546657867536
+1438598798454
+4457876987766
+3637877979653
+4654967986887
+4564679986453
+1224686865563
+2546548887735
+4322674655533";
+
+    let city = parse_input(input);
+    let route = city.best_route(1, 3).unwrap();
+    assert_eq!(route.heat_loss, 102);
+    assert_eq!(route.steps().iter().map(|(c, _)| city.block_weights[c]).sum::<u64>(), 102);
+    assert_eq!(route.moves(), "R2,D1,R3,U1,R3,D2,R2,D2,R1,D3,R1,D3,L1,D2,R1");
+    assert_eq!(city.render_route(&route), r"2>>34^>>>1323
+32v>>>35v5623
+32552456v>>54
+3446585845v52
+4546657867v>6
+14385987984v4
+44578769877v6
+36378779796v>
+465496798688v
+456467998645v
+12246868655<v
+25465488877v5
+43226746555v>
+");
+
+    let route = city.best_route(4, 10).unwrap();
+    assert_eq!(route.heat_loss, 94);
+    assert!(route.segments.iter().all(|s| (4..=10).contains(&s.run)));
+    assert_eq!(route.moves(), "R8,D4,R4,D8");
+}
+
+#[test]
+pub fn test_search() {
+    let input = r"2413432311323
+3215453535623
+3255245654254
+3446585845452
+4546657867536
+1438598798454
+4457876987766
+3637877979653
+4654967986887
+4564679986453
+1224686865563
+2546548887735
+4322674655533";
+
+    let city = parse_input(input);
+    let goals = [(12, 12).into(), (6, 6).into(), (12, 0).into(), (0, 12).into(), (3, 9).into()];
+    let start = Coord::from((4, 7));
+
+    for (min_run, max_run) in [(1, 3), (4, 10), (2, 5)] {
+        let everything = Search::new(start, goals, min_run, max_run);
+        let dijkstra = city.search(&everything);
+        let a_star = city.search(&everything.clone().with_heuristic());
+        for goal in goals {
+            let alone = city.search(&Search::new(start, [goal], min_run, max_run).with_heuristic());
+            assert_eq!(dijkstra.heat_loss(goal), alone.heat_loss(goal));
+            assert_eq!(a_star.heat_loss(goal), alone.heat_loss(goal));
+
+            if let Some(route) = alone.route(goal) {
+                assert_eq!(route.segments[0].start, start);
+                assert_eq!(route.steps().last().unwrap().0, goal);
+                assert_eq!(route.steps().iter().map(|(c, _)| city.block_weights[c]).sum::<u64>(), route.heat_loss);
+            }
+        }
+    }
+
+    // the same answers as the full search from the corner
+    let corner = Search::new((0, 0).into(), [city.max_size], 1, 3).with_heuristic();
+    assert_eq!(city.search(&corner).heat_loss(city.max_size), Some(102));
+    let corner = Search::new((0, 0).into(), [city.max_size], 4, 10).with_heuristic();
+    assert_eq!(city.search(&corner).heat_loss(city.max_size), Some(94));
+
+    // nothing to be found outside the city, or from outside it
+    let lost = Search::new((0, 0).into(), [(20, 20).into()], 1, 3);
+    assert_eq!(city.search(&lost).heat_loss((20, 20).into()), None);
+    let lost = Search::new((-1, 0).into(), [city.max_size], 1, 3);
+    assert_eq!(city.search(&lost).route(city.max_size), None);
+}
+
+#[test]
+pub fn test_pareto_routes() {
+    let input = r"24
+32";
+
+    let city = parse_input(input);
+    let frontier = city.pareto_routes((0, 0).into(), city.max_size, 1, 3);
+    assert_eq!(frontier.len(), 1);
+    assert_eq!(frontier[0].costs(), (5, 1, 2));
+    assert_eq!(frontier[0].route.moves(), "D1,R1");
+
+    let input = r"2413432311323
+3215453535623
+3255245654254
+3446585845452
+4546657867536
+1438598798454
+4457876987766
+3637877979653
+4654967986887
+4564679986453
+1224686865563
+2546548887735
+4322674655533";
+
+    let city = parse_input(input);
+    for ((min_run, max_run), best) in [((1, 3), 102), ((4, 10), 94)] {
+        let frontier = city.pareto_routes((0, 0).into(), city.max_size, min_run, max_run);
+        assert_eq!(frontier[0].heat_loss, best);
+        assert!(frontier.iter().all(|p| p.distance >= 24));
+
+        for (i, point) in frontier.iter().enumerate() {
+            let steps = point.route.steps();
+            assert_eq!(steps.last().unwrap().0, city.max_size);
+            assert_eq!(steps.iter().map(|(c, _)| city.block_weights[c]).sum::<u64>(), point.heat_loss);
+            assert_eq!(steps.len() as u64, point.distance);
+            assert_eq!(point.route.segments.len() as u64 - 1, point.turns);
+            assert!(point.route.segments.iter().all(|s| (min_run..=max_run).contains(&s.run)));
+
+            for (j, other) in frontier.iter().enumerate() {
+                assert!(i == j || !other.dominates(point));
+            }
+        }
+    }
+
+    // going nowhere costs nothing
+    let frontier = city.pareto_routes((3, 3).into(), (3, 3).into(), 1, 3);
+    assert_eq!(frontier.iter().map(|p| p.costs()).collect::<Vec<_>>(), vec![(0, 0, 0)]);
+}
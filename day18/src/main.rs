@@ -1,15 +1,17 @@
-use std::{collections::{HashMap, HashSet, BTreeSet}, ops::Add};
+use std::{collections::{BTreeMap, HashSet, BTreeSet}, fmt::Write, fs, ops::Add};
 use nom::{
     bytes::complete::tag,
     character::complete as cc,
     combinator::{all_consuming, map},
-    sequence::{separated_pair, tuple},
+    sequence::tuple,
     Finish,
     IResult,
 };
-use colored::*;
+use colored::Colorize;
 use itertools::Itertools;
 use std::time::Instant;
+#[cfg(test)]
+use std::collections::HashMap;
 
 #[derive(PartialEq, Eq, Copy, Clone, Hash, Debug, Ord, PartialOrd)]
 pub struct Coord {
@@ -67,25 +69,20 @@ impl From<char> for Direction {
     }
 }
 
+pub type Color = (u8, u8, u8);
+
 #[derive(Debug, Clone)]
 pub struct DigPlan {
-    instructions: Vec<(Direction, u64, (u8, u8, u8))>,
+    instructions: Vec<(Direction, u64, Color)>,
 }
 
 impl DigPlan {
     pub fn to_lagoon(&self) -> Lagoon {
-        let mut location: Coord = (0, 0).into();
-        let mut vertices = Vec::new();
+        let vertices = self.corners();
         let mut vertical_segments = Vec::new();
         let mut horizontal_segments = Vec::new();
-        vertices.push(location);
-        for (direction, amount, color) in &self.instructions {
-            let end_location = match direction {
-                Direction::Up => location + (0, -(*amount as i64)),
-                Direction::Down => location + (0, (*amount as i64)),
-                Direction::Right => location + ((*amount as i64), 0),
-                Direction::Left => location + (-(*amount as i64), 0),
-            };
+        let mut edges = Vec::new();
+        for ((location, end_location), (direction, _, color)) in vertices.iter().copied().tuple_windows().zip(&self.instructions) {
             match direction {
                 Direction::Up => vertical_segments.push(VerticalSegment::from(location, end_location)),
                 Direction::Down => vertical_segments.push(VerticalSegment::from(location, end_location)),
@@ -93,8 +90,7 @@ impl DigPlan {
                 Direction::Left => horizontal_segments.push(HorizontalSegment::from(location, end_location)),
             }
 
-            location = end_location;
-            vertices.push(location);
+            edges.push((location, end_location, *color));
         }
 
         let min_x = vertices.iter().map(|c| c.x).min().unwrap();
@@ -104,10 +100,141 @@ impl DigPlan {
 
         let bounds = ((min_x, min_y).into(), (max_x, max_y).into());
 
-        Lagoon { vertices, vertical_segments, horizontal_segments, bounds }
+        Lagoon { vertices, vertical_segments, horizontal_segments, edges, bounds }
     }    
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PlanError {
+    // the trench stops somewhere other than where it started
+    Unclosed { end: Coord },
+    // instructions `first` and `second` dig over the same ground, first
+    // meeting at `at`
+    SelfIntersection { first: usize, second: usize, at: Coord },
+}
+
+impl DigPlan {
+    // the corner reached after each instruction, starting from the origin
+    pub fn corners(&self) -> Vec<Coord> {
+        let mut location: Coord = (0, 0).into();
+        let mut corners = vec![location];
+        for (direction, amount, _) in &self.instructions {
+            let amount = *amount as i64;
+            location = match direction {
+                Direction::Up => location + (0, -amount),
+                Direction::Down => location + (0, amount),
+                Direction::Left => location + (-amount, 0),
+                Direction::Right => location + (amount, 0),
+            };
+            corners.push(location);
+        }
+        corners
+    }
+
+    // is this the simple closed loop to_lagoon needs? only neighbouring instructions may touch, at their corner
+    pub fn validate(&self) -> Result<(), Vec<PlanError>> {
+        let corners = self.corners();
+        let edges = corners.iter().copied().tuple_windows::<(_, _)>().collect::<Vec<_>>();
+        let end = *corners.last().unwrap();
+        let closed = end == corners[0];
+
+        let mut errors = vec![];
+        if !closed {
+            errors.push(PlanError::Unclosed { end });
+        }
+
+        for (first, &(a1, a2)) in edges.iter().enumerate() {
+            for (second, &(b1, b2)) in edges.iter().enumerate().skip(first + 1) {
+                let low = Coord::from((a1.x.min(a2.x).max(b1.x.min(b2.x)), a1.y.min(a2.y).max(b1.y.min(b2.y))));
+                let high = Coord::from((a1.x.max(a2.x).min(b1.x.max(b2.x)), a1.y.max(a2.y).min(b1.y.max(b2.y))));
+                if low.x > high.x || low.y > high.y {
+                    continue;
+                }
+
+                let shared_corner = if second == first + 1 {
+                    Some(a2)
+                } else if closed && first == 0 && second == edges.len() - 1 {
+                    Some(a1)
+                } else {
+                    None
+                };
+                if low == high && Some(low) == shared_corner {
+                    continue;
+                }
+
+                // for trenches that meet at their shared corner and then carry
+                // on over each other, report where they part from it
+                let at = match shared_corner {
+                    Some(corner) if low == corner => high,
+                    _ => low,
+                };
+                errors.push(PlanError::SelfIntersection { first, second, at });
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    // the trench plus whatever it cuts off, for any plan at all (even unclosed or self crossing):
+    // cut the plane into blocks around each corner and flood fill the outside a block at a time
+    pub fn dug_area(&self) -> u64 {
+        let corners = self.corners();
+        let boundaries = |axis: fn(&Coord) -> i64| {
+            corners.iter().map(axis)
+                .flat_map(|v| [v - 1, v, v + 1, v + 2])
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+        // block i covers [xs[i], xs[i + 1]), and the outermost ring of blocks
+        // is never dug, so it's all connected to the outside
+        let xs = boundaries(|c| c.x);
+        let ys = boundaries(|c| c.y);
+        let (width, height) = (xs.len() - 1, ys.len() - 1);
+        let block_of = |values: &[i64], v: i64| values.binary_search(&v).unwrap();
+
+        let mut trench = vec![false; width * height];
+        for (start, end) in corners.iter().tuple_windows() {
+            let (x1, x2) = (block_of(&xs, start.x.min(end.x)), block_of(&xs, start.x.max(end.x) + 1));
+            let (y1, y2) = (block_of(&ys, start.y.min(end.y)), block_of(&ys, start.y.max(end.y) + 1));
+            for y in y1..y2 {
+                for x in x1..x2 {
+                    trench[y * width + x] = true;
+                }
+            }
+        }
+
+        let mut outside = vec![false; width * height];
+        let mut to_visit = vec![(0, 0)];
+        outside[0] = true;
+        while let Some((x, y)) = to_visit.pop() {
+            let neighbours = [
+                (x > 0).then(|| (x - 1, y)),
+                (x + 1 < width).then_some((x + 1, y)),
+                (y > 0).then(|| (x, y - 1)),
+                (y + 1 < height).then_some((x, y + 1)),
+            ];
+            for (nx, ny) in neighbours.into_iter().flatten() {
+                let block = ny * width + nx;
+                if !trench[block] && !outside[block] {
+                    outside[block] = true;
+                    to_visit.push((nx, ny));
+                }
+            }
+        }
+
+        let mut area = 0;
+        for y in 0..height {
+            for x in 0..width {
+                if !outside[y * width + x] {
+                    area += ((xs[x + 1] - xs[x]) * (ys[y + 1] - ys[y])) as u64;
+                }
+            }
+        }
+        area
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct VerticalSegment {
     lower: Coord,
@@ -156,70 +283,316 @@ impl HorizontalSegment {
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Pixel {
+    Outside,
+    Interior,
+    Trench(Color),
+}
+
+// the lagoon drawn onto a grid, each pixel covering a `scale` x `scale` square of cells
+#[derive(Debug, Clone)]
+pub struct Raster {
+    scale: u64,
+    width: usize,
+    height: usize,
+    pixels: Vec<Pixel>,
+}
+
+impl Raster {
+    pub fn scale(&self) -> u64 {
+        self.scale
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Pixel {
+        self.pixels[y * self.width + x]
+    }
+
+    // trench in its own colour (if asked for), the water inside it as '~'
+    pub fn to_terminal(&self, colour: bool) -> String {
+        let mut output = String::new();
+        for row in self.pixels.chunks(self.width) {
+            for pixel in row {
+                match pixel {
+                    Pixel::Outside => output.push('.'),
+                    Pixel::Interior => output.push('~'),
+                    Pixel::Trench((r, g, b)) if colour => output += &"#".truecolor(*r, *g, *b).to_string(),
+                    Pixel::Trench(_) => output.push('#'),
+                }
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    // a binary PPM, with the outside black and the inside grey
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut output = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for pixel in &self.pixels {
+            let (r, g, b) = match pixel {
+                Pixel::Outside => (0, 0, 0),
+                Pixel::Interior => (96, 96, 96),
+                Pixel::Trench(color) => *color,
+            };
+            output.extend([r, g, b]);
+        }
+        output
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Lagoon {
     vertices: Vec<Coord>,
     vertical_segments: Vec<VerticalSegment>,
     bounds: (Coord, Coord),
     horizontal_segments: Vec<HorizontalSegment>,
+    // each trench in the order it was dug, with its colour
+    edges: Vec<(Coord, Coord, Color)>,
 }
 
 impl Lagoon {
+    // the top left and bottom right corners of the trench
+    pub fn bounds(&self) -> (Coord, Coord) {
+        self.bounds
+    }
+
+    // only rows with a corner can differ from the one above, so count one row per band between them
     pub fn find_inside(&self) -> u64 {
+        let event_rows = self.vertices.iter().map(|c| c.y).collect::<BTreeSet<_>>();
+
         let mut interior_size = 0u64;
+        let mut previous_row: Option<i64> = None;
+        for &y in &event_rows {
+            if let Some(previous) = previous_row {
+                let band_height = (y - previous - 1) as u64;
+                if band_height > 0 {
+                    interior_size += band_height * self.cells_in_row(previous + 1);
+                }
+            }
+            interior_size += self.cells_in_row(y);
+            previous_row = Some(y);
+        }
+
+        interior_size
+    }
 
-        // could make this faster by skipping any y that doesn't have vertices in
-        // but we'd need to be more careful about tracking what's "open this row" - we don't want to drag a whole
-        // block of horizontal row up
-        for y in self.bounds.0.y ..= self.bounds.1.y {
-            let mut vertical_walls_intersecting = self.vertical_segments.iter().filter_map(|s| s.intercept(y)).collect::<BTreeSet<_>>();
-            let vertices_in_row = self.vertices.iter().filter(|c| c.y == y).map(|c| c.x).collect::<HashSet<_>>();
-            let vertical_segment_lower_vertices = self.vertical_segments.iter()
-                .filter(|s| s.lower.y == y)
-                .map(|s| s.lower.x).collect::<HashSet<_>>();
-
-            interior_size += vertical_walls_intersecting.len() as u64;
-
-            let mut inside = false;
-            let mut last_wall = None;
-            while let Some(intercept) = vertical_walls_intersecting.pop_first() {
-                if let Some(wall) = last_wall {
-                    if inside {
-                        interior_size += (intercept - wall - 1) as u64;
-                    }
+    // at most `max_dimension` pixels a side, with the trench drawn over the top so thin edges survive
+    pub fn rasterize(&self, max_dimension: u64) -> Raster {
+        let (low, high) = self.bounds;
+        let extent_x = (high.x - low.x + 1) as u64;
+        let extent_y = (high.y - low.y + 1) as u64;
+        let scale = extent_x.max(extent_y).div_ceil(max_dimension.max(1));
+        let width = extent_x.div_ceil(scale) as usize;
+        let height = extent_y.div_ceil(scale) as usize;
+        let mut pixels = vec![Pixel::Outside; width * height];
+
+        let centre = (scale / 2) as i64;
+        for py in 0..height {
+            let y = low.y + (py as u64 * scale) as i64 + centre;
+            // walls crossing just below the middle of this row, so corners
+            // are only counted once
+            let crossings = self.vertical_segments.iter()
+                .filter(|s| s.lower.y <= y && y < s.upper.y)
+                .map(|s| s.lower.x)
+                .sorted()
+                .collect::<Vec<_>>();
+
+            let mut crossed = 0;
+            for px in 0..width {
+                let x = low.x + (px as u64 * scale) as i64 + centre;
+                while crossed < crossings.len() && crossings[crossed] < x {
+                    crossed += 1;
+                }
+                if crossed % 2 == 1 {
+                    pixels[py * width + px] = Pixel::Interior;
                 }
+            }
+        }
 
-                if vertices_in_row.contains(&intercept) {
-                    // this must be the start of a horizontal line
-                    // so the next intercept must be its end
-                    let next = vertical_walls_intersecting.pop_first().unwrap();
-                    assert!(vertices_in_row.contains(&next));
-
-                    // include this many cells
-                    // we include this regardless of whether we were previously "inside" or "outside"
-                    interior_size += (next - intercept - 1) as u64;
-
-                    match (vertical_segment_lower_vertices.contains(&intercept), vertical_segment_lower_vertices.contains(&next)) {
-                        // both vertices are the bottom or the top of a segment -
-                        // so this doesn't change our inside/outside parity
-                        (true, true) | (false, false) => { },
-                        // otherwise this was a horizontal jog in a vertical line
-                        // so we have changed whether we're inside or outside
-                        (true, false) | (false, true) => { inside = !inside; },
-                    }
-
-                    last_wall = Some(next);
-                } else {
-                    // this is a vertical wall going past us
-                    inside = !inside;
-                    last_wall = Some(intercept);
+        let to_pixel = |c: Coord| (
+            ((c.x - low.x) as u64 / scale) as usize,
+            ((c.y - low.y) as u64 / scale) as usize,
+        );
+        for (start, end, color) in &self.edges {
+            let (x1, y1) = to_pixel(*start);
+            let (x2, y2) = to_pixel(*end);
+            for py in y1.min(y2) ..= y1.max(y2) {
+                for px in x1.min(x2) ..= x1.max(x2) {
+                    pixels[py * width + px] = Pixel::Trench(*color);
                 }
             }
         }
 
-        interior_size
+        Raster { scale, width, height, pixels }
+    }
+
+    // the trench in plan coordinates, leaving the viewer to scale it to `max_dimension` pixels
+    pub fn to_svg(&self, max_dimension: u64) -> String {
+        let (low, high) = self.bounds;
+        let extent_x = (high.x - low.x + 1) as f64;
+        let extent_y = (high.y - low.y + 1) as f64;
+        let factor = max_dimension as f64 / extent_x.max(extent_y);
+
+        let mut output = String::new();
+        writeln!(output,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{:.0}" height="{:.0}" viewBox="{} {} {} {}">"#,
+            extent_x * factor, extent_y * factor,
+            low.x as f64 - 0.5, low.y as f64 - 0.5, extent_x, extent_y).unwrap();
+        let points = self.vertices.iter().map(|c| format!("{},{}", c.x, c.y)).join(" ");
+        writeln!(output, r#"<polygon points="{points}" fill="rgb(96,96,96)" fill-rule="evenodd"/>"#).unwrap();
+        for (start, end, (r, g, b)) in &self.edges {
+            writeln!(output,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="rgb({r},{g},{b})" stroke-width="2" vector-effect="non-scaling-stroke" stroke-linecap="square"/>"#,
+                start.x, start.y, end.x, end.y).unwrap();
+        }
+        output.push_str("</svg>\n");
+        output
+    }
+
+    // how many cells in row y are either trench or enclosed by it
+    fn cells_in_row(&self, y: i64) -> u64 {
+        self.row_intervals(y).iter().map(|(from, to)| (to - from + 1) as u64).sum()
+    }
+
+    // the runs of cells in row y that are either trench or enclosed by it, as
+    // inclusive (from, to) pairs in order, with touching runs joined up
+    pub fn row_intervals(&self, y: i64) -> Vec<(i64, i64)> {
+        let mut runs = vec![];
+
+        let mut vertical_walls_intersecting = self.vertical_segments.iter().filter_map(|s| s.intercept(y)).collect::<BTreeSet<_>>();
+        let vertices_in_row = self.vertices.iter().filter(|c| c.y == y).map(|c| c.x).collect::<HashSet<_>>();
+        let vertical_segment_lower_vertices = self.vertical_segments.iter()
+            .filter(|s| s.lower.y == y)
+            .map(|s| s.lower.x).collect::<HashSet<_>>();
+
+        let mut inside = false;
+        let mut last_wall = None;
+        while let Some(intercept) = vertical_walls_intersecting.pop_first() {
+            if let Some(wall) = last_wall {
+                if inside && intercept > wall + 1 {
+                    runs.push((wall + 1, intercept - 1));
+                }
+            }
+
+            if vertices_in_row.contains(&intercept) {
+                // this must be the start of a horizontal line
+                // so the next intercept must be its end
+                let next = vertical_walls_intersecting.pop_first().unwrap();
+                assert!(vertices_in_row.contains(&next));
+
+                // include the whole line
+                // we include this regardless of whether we were previously "inside" or "outside"
+                runs.push((intercept, next));
+
+                match (vertical_segment_lower_vertices.contains(&intercept), vertical_segment_lower_vertices.contains(&next)) {
+                    // both vertices are the bottom or the top of a segment -
+                    // so this doesn't change our inside/outside parity
+                    (true, true) | (false, false) => { },
+                    // otherwise this was a horizontal jog in a vertical line
+                    // so we have changed whether we're inside or outside
+                    (true, false) | (false, true) => { inside = !inside; },
+                }
+
+                last_wall = Some(next);
+            } else {
+                // this is a vertical wall going past us
+                runs.push((intercept, intercept));
+                inside = !inside;
+                last_wall = Some(intercept);
+            }
+        }
+
+        // the runs are already in order, so only neighbours can touch
+        let mut intervals: Vec<(i64, i64)> = vec![];
+        for (from, to) in runs {
+            match intervals.last_mut() {
+                Some((_, end)) if from <= *end + 1 => *end = (*end).max(to),
+                _ => intervals.push((from, to)),
+            }
+        }
+        intervals
+    }
+}
+
+// several dig plans, each dug from its own origin
+#[derive(Debug, Clone, Default)]
+pub struct Site {
+    lagoons: Vec<(Coord, Lagoon)>,
+}
+
+impl Site {
+    pub fn new() -> Site {
+        Site::default()
+    }
+
+    pub fn add(&mut self, plan: &DigPlan, origin: Coord) -> &mut Site {
+        self.lagoons.push((origin, plan.to_lagoon()));
+        self
+    }
+
+    // how much ground is dug by exactly k plans, for each k - one row per band, like find_inside
+    pub fn coverage(&self) -> BTreeMap<usize, u64> {
+        let event_rows = self.lagoons.iter()
+            .flat_map(|(origin, lagoon)| lagoon.vertices.iter().map(move |c| c.y + origin.y))
+            .collect::<BTreeSet<_>>();
+
+        let mut coverage = BTreeMap::new();
+        let mut previous_row: Option<i64> = None;
+        for &y in &event_rows {
+            if let Some(previous) = previous_row {
+                let band_height = (y - previous - 1) as u64;
+                if band_height > 0 {
+                    self.add_row_coverage(previous + 1, band_height, &mut coverage);
+                }
+            }
+            self.add_row_coverage(y, 1, &mut coverage);
+            previous_row = Some(y);
+        }
+
+        coverage
+    }
+
+    fn add_row_coverage(&self, y: i64, rows: u64, coverage: &mut BTreeMap<usize, u64>) {
+        // +1 where a run starts, -1 just past where it ends
+        let mut changes = BTreeMap::new();
+        for (origin, lagoon) in &self.lagoons {
+            for (from, to) in lagoon.row_intervals(y - origin.y) {
+                *changes.entry(from + origin.x).or_insert(0) += 1;
+                *changes.entry(to + origin.x + 1).or_insert(0) -= 1;
+            }
+        }
+
+        let mut depth = 0_i64;
+        let mut last_x = None;
+        for (x, change) in changes {
+            if let Some(last) = last_x {
+                if depth > 0 {
+                    *coverage.entry(depth as usize).or_insert(0) += (x - last) as u64 * rows;
+                }
+            }
+            depth += change;
+            last_x = Some(x);
+        }
+    }
+
+    // ground dug by any plan at all
+    pub fn union_area(&self) -> u64 {
+        self.coverage().values().sum()
     }
 
+    // ground dug by more than one plan
+    pub fn overlap_area(&self) -> u64 {
+        self.coverage().range(2..).map(|(_, area)| area).sum()
+    }
+
+    // ground dug by exactly k plans
+    pub fn area_dug_exactly(&self, k: usize) -> u64 {
+        self.coverage().get(&k).copied().unwrap_or(0)
+    }
 }
 
 pub fn parse_hex_digit(input: &str) -> IResult<&str, u8> {
@@ -227,7 +600,7 @@ pub fn parse_hex_digit(input: &str) -> IResult<&str, u8> {
     Ok((rest, (first * 16 + second).try_into().unwrap()))
 }
 
-pub fn parse_instruction(input: &str) -> IResult<&str, (Direction, u64, (u8, u8, u8))> {
+pub fn parse_instruction(input: &str) -> IResult<&str, (Direction, u64, Color)> {
     let (rest, 
         (
         direction,
@@ -281,7 +654,7 @@ pub fn parse_input_inverted(input: &str) -> DigPlan {
                 };
                 let amount = digit5 + digit4 * 16 + digit3 * 16 * 16
                     + digit2 * 16 * 16 * 16 + digit1 * 16 * 16 * 16 * 16;
-                instructions.push((direction, amount, (127, 127, 127)));
+                instructions.push((direction, amount, (r, g, b)));
             }
             Err(e) => { dbg!(e); unreachable!() }
         }
@@ -295,23 +668,54 @@ pub fn part_1_interior(lagoon: &Lagoon) -> u64 {
 }
 
 pub fn part_1_pick(lagoon: &Lagoon) -> u64 {
-    let area: i64 = lagoon.vertices.iter().tuple_windows().map(|(c1, c2)| ((c1.y + c2.y) * (c1.x - c2.x))).sum::<i64>() / 2i64;
+    let area: i64 = lagoon.vertices.iter().tuple_windows().map(|(c1, c2)| (c1.y + c2.y) * (c1.x - c2.x)).sum::<i64>() / 2i64;
     let perimeter: u64 = lagoon.horizontal_segments.iter().map(|s| (s.upper.x - s.lower.x) as u64).sum::<u64>()
         + lagoon.vertical_segments.iter().map(|s| (s.upper.y - s.lower.y) as u64).sum::<u64>();
     area as u64 + (perimeter / 2) + 1
 }
 
+// small lagoons get printed, anything bigger is written to `<name>.ppm` and `<name>.svg`
+const TERMINAL_WIDTH: u64 = 160;
+const IMAGE_SIZE: u64 = 1000;
+
+pub fn render(lagoon: &Lagoon, name: &str) -> std::io::Result<()> {
+    let (low, high) = lagoon.bounds();
+    if ((high.x - low.x + 1) as u64) <= TERMINAL_WIDTH {
+        print!("{}", lagoon.rasterize(TERMINAL_WIDTH).to_terminal(colored::control::SHOULD_COLORIZE.should_colorize()));
+    } else {
+        let raster = lagoon.rasterize(IMAGE_SIZE);
+        fs::write(format!("{name}.ppm"), raster.to_ppm())?;
+        fs::write(format!("{name}.svg"), lagoon.to_svg(IMAGE_SIZE))?;
+        println!("Wrote {name}.ppm and {name}.svg at {} cells per pixel", raster.scale());
+    }
+    Ok(())
+}
+
 fn main() {
     let input = include_str!("../input.txt");
+    let render_lagoons = std::env::args().any(|arg| arg == "--render");
     let digplan = parse_input(input);
+    if let Err(errors) = digplan.validate() {
+        println!("Part 1 plan isn't a simple loop: {errors:?}");
+        println!("Part 1 (dug area): {}", digplan.dug_area());
+    }
     let lagoon = digplan.to_lagoon();
     println!("Part 1: {}", part_1_interior(&lagoon));
+    if render_lagoons {
+        render(&lagoon, "part_1").unwrap();
+    }
 
     let digplan2 = parse_input_inverted(input);
     let lagoon2 = digplan2.to_lagoon();
     let now = Instant::now();
     println!("Part 2: {}", part_1_pick(&lagoon2));
     println!("Part 2 took: {:2?}", now.elapsed());
+    let now = Instant::now();
+    println!("Part 2 (scanline): {}", lagoon2.find_inside());
+    println!("Part 2 (scanline) took: {:2?}", now.elapsed());
+    if render_lagoons {
+        render(&lagoon2, "part_2").unwrap();
+    }
 }
 
 #[test]
@@ -333,10 +737,171 @@ U 2 (#7a21e3)";
 
     let digplan = parse_input(input);
     let lagoon = digplan.to_lagoon();
-    println!("{}", part_1_interior(&lagoon));
-    println!("{}", part_1_pick(&lagoon));
+    assert_eq!(part_1_interior(&lagoon), 62);
+    assert_eq!(part_1_pick(&lagoon), 62);
 
     let digplan2 = parse_input_inverted(input);
     let lagoon2 = digplan2.to_lagoon();
-    println!("{}", part_1_pick(&lagoon2));
-}
\ No newline at end of file
+    assert_eq!(lagoon2.find_inside(), 952408144115);
+    assert_eq!(part_1_pick(&lagoon2), 952408144115);
+}
+
+#[test]
+pub fn test_render() {
+    let input = r"R 6 (#70c710)
+D 5 (#0dc571)
+L 2 (#5713f0)
+D 2 (#d2c081)
+R 2 (#59c680)
+D 2 (#411b91)
+L 5 (#8ceee2)
+U 2 (#caa173)
+L 1 (#1b58a2)
+U 2 (#caa171)
+R 2 (#7807d2)
+U 3 (#a77fa3)
+L 2 (#015232)
+U 2 (#7a21e3)";
+
+    let lagoon = parse_input(input).to_lagoon();
+    let raster = lagoon.rasterize(80);
+    assert_eq!(raster.scale(), 1);
+    assert_eq!(raster.to_terminal(false), r"#######
+#~~~~~#
+###~~~#
+..#~~~#
+..#~~~#
+###~###
+#~~~#..
+##~~###
+.#~~~~#
+.######
+");
+    assert_eq!(raster.get(3, 0), Pixel::Trench((0x70, 0xc7, 0x10)));
+    assert_eq!(raster.get(3, 9), Pixel::Trench((0x8c, 0xee, 0xe2)));
+
+    let ppm = raster.to_ppm();
+    assert!(ppm.starts_with(b"P6\n7 10\n255\n"));
+    assert_eq!(ppm.len(), b"P6\n7 10\n255\n".len() + 7 * 10 * 3);
+
+    // part 2 is far too big to draw a cell at a time
+    let lagoon2 = parse_input_inverted(input).to_lagoon();
+    let raster2 = lagoon2.rasterize(100);
+    let (width, height) = raster2.dimensions();
+    assert!(width <= 100 && height <= 100);
+    assert_eq!(raster2.scale(), 1186329_u64.div_ceil(100));
+    assert_eq!(raster2.get(width / 4, height / 2), Pixel::Interior);
+    assert_eq!(raster2.get(width / 2, height / 2), Pixel::Outside);
+    assert_eq!(raster2.get(20, 0), Pixel::Trench((0x70, 0xc7, 0x10)));
+
+    let svg = lagoon2.to_svg(1000);
+    assert_eq!(svg.matches("<line").count(), 14);
+    assert!(svg.contains(r#"stroke="rgb(112,199,16)""#));
+}
+
+#[test]
+pub fn test_validate() {
+    let input = r"R 6 (#70c710)
+D 5 (#0dc571)
+L 2 (#5713f0)
+D 2 (#d2c081)
+R 2 (#59c680)
+D 2 (#411b91)
+L 5 (#8ceee2)
+U 2 (#caa173)
+L 1 (#1b58a2)
+U 2 (#caa171)
+R 2 (#7807d2)
+U 3 (#a77fa3)
+L 2 (#015232)
+U 2 (#7a21e3)";
+
+    let digplan = parse_input(input);
+    assert_eq!(digplan.validate(), Ok(()));
+    assert_eq!(digplan.dug_area(), 62);
+    let digplan2 = parse_input_inverted(input);
+    assert_eq!(digplan2.validate(), Ok(()));
+    assert_eq!(digplan2.dug_area(), 952408144115);
+
+    let unclosed = parse_input("R 2 (#000000)\nD 2 (#000000)");
+    assert_eq!(unclosed.validate(), Err(vec![PlanError::Unclosed { end: (2, 2).into() }]));
+    assert_eq!(unclosed.dug_area(), 5);
+
+    let doubled_back = parse_input("R 3 (#000000)\nL 3 (#000000)");
+    assert_eq!(doubled_back.validate(), Err(vec![PlanError::SelfIntersection { first: 0, second: 1, at: (0, 0).into() }]));
+    assert_eq!(doubled_back.dug_area(), 4);
+
+    // a figure of eight, with the trench going up through the first loop
+    let crossing = parse_input("R 4 (#000000)
+D 4 (#000000)
+L 2 (#000000)
+U 6 (#000000)
+L 2 (#000000)
+D 2 (#000000)");
+    assert_eq!(crossing.validate(), Err(vec![PlanError::SelfIntersection { first: 0, second: 3, at: (2, 0).into() }]));
+    assert_eq!(crossing.dug_area(), 23);
+    // the shoelace counts each loop once with opposite signs
+    assert_ne!(part_1_pick(&crossing.to_lagoon()), 23);
+}
+
+#[test]
+pub fn test_site() {
+    let input = r"R 6 (#70c710)
+D 5 (#0dc571)
+L 2 (#5713f0)
+D 2 (#d2c081)
+R 2 (#59c680)
+D 2 (#411b91)
+L 5 (#8ceee2)
+U 2 (#caa173)
+L 1 (#1b58a2)
+U 2 (#caa171)
+R 2 (#7807d2)
+U 3 (#a77fa3)
+L 2 (#015232)
+U 2 (#7a21e3)";
+
+    // count the cells of each lagoon one at a time to check against
+    let cells = |plan: &DigPlan, origin: Coord| {
+        let lagoon = plan.to_lagoon();
+        let raster = lagoon.rasterize(1000);
+        let (low, _) = lagoon.bounds();
+        let (width, height) = raster.dimensions();
+        (0..height).flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| raster.get(x, y) != Pixel::Outside)
+            .map(|(x, y)| Coord::from((low.x + x as i64 + origin.x, low.y + y as i64 + origin.y)))
+            .collect::<HashSet<_>>()
+    };
+
+    let digplan = parse_input(input);
+    let origins = [(0, 0).into(), (3, 2).into(), (-2, 4).into()];
+    let mut site = Site::new();
+    let mut counts = HashMap::new();
+    for origin in origins {
+        site.add(&digplan, origin);
+        for cell in cells(&digplan, origin) {
+            *counts.entry(cell).or_insert(0) += 1;
+        }
+    }
+    for k in 1..=3 {
+        assert_eq!(site.area_dug_exactly(k), counts.values().filter(|&&n| n == k).count() as u64);
+    }
+    assert_eq!(site.union_area(), counts.len() as u64);
+    assert_eq!(site.overlap_area(), counts.values().filter(|&&n| n > 1).count() as u64);
+
+    // part 2 sized plans: the same one twice over, and then once more well
+    // out of the way
+    let digplan2 = parse_input_inverted(input);
+    let mut site = Site::new();
+    site.add(&digplan2, (0, 0).into()).add(&digplan2, (0, 0).into());
+    assert_eq!(site.coverage(), BTreeMap::from([(2, 952408144115)]));
+    site.add(&digplan2, (5_000_000, -3_000_000).into());
+    assert_eq!(site.union_area(), 2 * 952408144115);
+    assert_eq!(site.overlap_area(), 952408144115);
+    assert_eq!(site.area_dug_exactly(1), 952408144115);
+
+    // shifting one copy along a little overlaps all but a strip of each
+    let mut site = Site::new();
+    site.add(&digplan2, (0, 0).into()).add(&digplan2, (1000, 0).into());
+    assert_eq!(site.union_area() + site.overlap_area(), 2 * 952408144115);
+}
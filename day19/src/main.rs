@@ -52,6 +52,10 @@ impl RatingRange {
             r.end() - r.start() + 1
         }).unwrap_or(0)
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 #[derive(PartialEq, Eq, Copy, Clone, Hash, Debug, Ord, PartialOrd)]
@@ -113,6 +117,15 @@ pub struct PartRatings {
 }
 
 impl PartRatings {
+    pub fn rating(&self, rating_type: RatingType) -> u64 {
+        match rating_type {
+            RatingType::XtremelyCool => self.xtremely_cool,
+            RatingType::Musical => self.musical,
+            RatingType::Aerodynamic => self.aerodynamic,
+            RatingType::Shiny => self.shiny,
+        }
+    }
+
     pub fn value(&self) -> u64 {
         self.xtremely_cool + self.musical + self.aerodynamic + self.shiny
     }
@@ -236,6 +249,193 @@ impl Input {
     pub fn get_thresholds(&self) -> Vec<ConditionThresholds> {
         self.invert_rule(self.rules.get("in").unwrap())
     }
+
+    pub fn compile(&self) -> Result<DecisionProgram, CompileError> {
+        DecisionProgram::compile(&self.rules)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum CompileError {
+    UnknownWorkflow(String),
+    // a workflow that can end up sending a part back to itself
+    Cycle(String),
+    // a workflow with nowhere to send a part that fails every condition
+    NoFallback(String),
+}
+
+// the workflow and step that accepted or rejected a part
+#[derive(PartialEq, Eq, Copy, Clone, Hash, Debug)]
+pub struct Terminal<'a> {
+    pub accepted: bool,
+    pub workflow: &'a str,
+    pub step: usize,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Hash, Debug)]
+enum Node {
+    Test { field: RatingType, condition_type: ConditionType, threshold: u64, pass: u32, fail: u32 },
+    Jump(u32),
+    Terminal { accepted: bool },
+}
+
+// the workflows flattened into nodes, with a terminal node per A or R step so
+// the node a part finishes on says which step decided it
+#[derive(Clone, Debug)]
+pub struct DecisionProgram {
+    nodes: Vec<Node>,
+    // which workflow and step each node came from
+    origins: Vec<(u32, u32)>,
+    workflow_names: Vec<String>,
+    entry: u32,
+}
+
+impl DecisionProgram {
+    pub fn compile(rules: &HashMap<String, Rule>) -> Result<DecisionProgram, CompileError> {
+        let mut program = DecisionProgram { nodes: vec![], origins: vec![], workflow_names: vec![], entry: 0 };
+        let mut entries = HashMap::new();
+        program.entry = program.compile_workflow(rules, "in", &mut entries, &mut vec![])?;
+        Ok(program)
+    }
+
+    fn compile_workflow(
+        &mut self,
+        rules: &HashMap<String, Rule>,
+        name: &str,
+        entries: &mut HashMap<String, u32>,
+        in_progress: &mut Vec<String>,
+    ) -> Result<u32, CompileError> {
+        if let Some(&entry) = entries.get(name) {
+            return Ok(entry);
+        }
+        if in_progress.iter().any(|n| n == name) {
+            return Err(CompileError::Cycle(name.to_owned()));
+        }
+        let rule = rules.get(name).ok_or_else(|| CompileError::UnknownWorkflow(name.to_owned()))?;
+        // anything after the first unconditional step can never be reached
+        let last_step = rule.steps.iter().position(|s| s.condition.is_none())
+            .ok_or_else(|| CompileError::NoFallback(name.to_owned()))?;
+        in_progress.push(name.to_owned());
+
+        let workflow = self.workflow_names.len() as u32;
+        self.workflow_names.push(name.to_owned());
+
+        // build the steps from the last one backwards, so each test knows
+        // where to go when it fails
+        let mut next = None;
+        for (index, step) in rule.steps.iter().enumerate().take(last_step + 1).rev() {
+            let origin = (workflow, index as u32);
+            let target = match step.target_rule.as_str() {
+                "A" | "R" => self.push(Node::Terminal { accepted: step.target_rule == "A" }, origin),
+                target => {
+                    let entry = self.compile_workflow(rules, target, entries, in_progress)?;
+                    if step.condition.is_some() {
+                        entry
+                    } else {
+                        self.push(Node::Jump(entry), origin)
+                    }
+                },
+            };
+            next = Some(match (step.condition, next) {
+                (Some(condition), Some(fail)) => self.push(
+                    Node::Test {
+                        field: condition.field,
+                        condition_type: condition.condition_type,
+                        threshold: condition.threshold,
+                        pass: target,
+                        fail,
+                    },
+                    origin),
+                _ => target,
+            });
+        }
+
+        in_progress.pop();
+        let entry = next.ok_or_else(|| CompileError::NoFallback(name.to_owned()))?;
+        entries.insert(name.to_owned(), entry);
+        Ok(entry)
+    }
+
+    fn push(&mut self, node: Node, origin: (u32, u32)) -> u32 {
+        self.nodes.push(node);
+        self.origins.push(origin);
+        (self.nodes.len() - 1) as u32
+    }
+
+    // the terminal node this part finishes on
+    pub fn classify(&self, part: &PartRatings) -> u32 {
+        let mut node = self.entry;
+        loop {
+            node = match self.nodes[node as usize] {
+                Node::Test { field, condition_type, threshold, pass, fail } => {
+                    if Self::passes(part, field, condition_type, threshold) { pass } else { fail }
+                },
+                Node::Jump(target) => target,
+                Node::Terminal { .. } => return node,
+            };
+        }
+    }
+
+    fn passes(part: &PartRatings, field: RatingType, condition_type: ConditionType, threshold: u64) -> bool {
+        match condition_type {
+            ConditionType::GreaterThan => part.rating(field) > threshold,
+            ConditionType::LessThan => part.rating(field) < threshold,
+        }
+    }
+
+    pub fn accepts(&self, part: &PartRatings) -> bool {
+        self.is_accepting(self.classify(part))
+    }
+
+    // just the terminal each part finishes on - use trace_batch for the paths too
+    pub fn classify_batch(&self, parts: &[PartRatings]) -> Vec<u32> {
+        parts.iter().map(|part| self.classify(part)).collect()
+    }
+
+    pub fn trace_batch(&self, parts: &[PartRatings]) -> Vec<(Vec<(&str, usize)>, Terminal<'_>)> {
+        parts.iter().map(|part| self.trace(part)).collect()
+    }
+
+    pub fn is_accepting(&self, terminal: u32) -> bool {
+        matches!(self.nodes[terminal as usize], Node::Terminal { accepted: true })
+    }
+
+    pub fn terminal(&self, terminal: u32) -> Terminal<'_> {
+        let (workflow, step) = self.origins[terminal as usize];
+        Terminal {
+            accepted: self.is_accepting(terminal),
+            workflow: &self.workflow_names[workflow as usize],
+            step: step as usize,
+        }
+    }
+
+    // every (workflow, step) that sent the part on to another workflow, in
+    // order, and then the step that accepted or rejected it
+    pub fn trace(&self, part: &PartRatings) -> (Vec<(&str, usize)>, Terminal<'_>) {
+        let mut path = vec![];
+        let mut node = self.entry;
+        loop {
+            let (workflow, step) = self.origins[node as usize];
+            let from = (self.workflow_names[workflow as usize].as_str(), step as usize);
+            node = match self.nodes[node as usize] {
+                Node::Test { field, condition_type, threshold, pass, fail } => {
+                    if !Self::passes(part, field, condition_type, threshold) {
+                        fail
+                    } else {
+                        if !matches!(self.nodes[pass as usize], Node::Terminal { .. }) {
+                            path.push(from);
+                        }
+                        pass
+                    }
+                },
+                Node::Jump(target) => {
+                    path.push(from);
+                    target
+                },
+                Node::Terminal { .. } => return (path, self.terminal(node)),
+            };
+        }
+    }
 }
 
 pub fn part_1(input: &Input) -> u64 {
@@ -250,6 +450,15 @@ pub fn part_1(input: &Input) -> u64 {
     accepted_parts.iter().map(|p| p.value()).sum()
 }
 
+pub fn part_1_compiled(input: &Input) -> u64 {
+    let program = input.compile().unwrap();
+    program.classify_batch(&input.parts).into_iter()
+        .zip(&input.parts)
+        .filter(|(terminal, _)| program.is_accepting(*terminal))
+        .map(|(_, part)| part.value())
+        .sum()
+}
+
 pub fn part_2(input: &Input) -> u64 {
     input.get_thresholds().iter().map(|ct| ct.number_combinations()).sum()
 }
@@ -355,6 +564,7 @@ fn main() {
     let input = include_str!("../input.txt");
     let input = parse_input(input);
     println!("Part 1: {}", part_1(&input));
+    println!("Part 1 (compiled): {}", part_1_compiled(&input));
     println!("Part 2: {}", part_2(&input));
 }
 
@@ -381,4 +591,85 @@ hdj{m>838:A,pv}
     let input = parse_input(input);
     assert_eq!(part_1(&input), 19114);
     assert_eq!(part_2(&input), 167409079868000);
-}
\ No newline at end of file
+}
+
+#[test]
+pub fn test_compiled() {
+    let input_text = r"px{a<2006:qkq,m>2090:A,rfg}
+pv{a>1716:R,A}
+lnx{m>1548:A,A}
+rfg{s<537:gd,x>2440:R,A}
+qs{s>3448:A,lnx}
+qkq{x<1416:A,crn}
+crn{x>2662:A,R}
+in{s<1351:px,qqz}
+qqz{s>2770:qs,m<1801:hdj,R}
+gd{a>3333:R,R}
+hdj{m>838:A,pv}
+
+{x=787,m=2655,a=1222,s=2876}
+{x=1679,m=44,a=2067,s=496}
+{x=2036,m=264,a=79,s=2244}
+{x=2461,m=1339,a=466,s=291}
+{x=2127,m=1623,a=2188,s=1013}";
+
+    let input = parse_input(input_text);
+    assert_eq!(part_1_compiled(&input), 19114);
+
+    let program = input.compile().unwrap();
+    let (path, terminal) = program.trace(&input.parts[0]);
+    assert_eq!(path, vec![("in", 1), ("qqz", 0), ("qs", 1)]);
+    assert_eq!(terminal, Terminal { accepted: true, workflow: "lnx", step: 0 });
+    let (path, terminal) = program.trace(&input.parts[1]);
+    assert_eq!(path, vec![("in", 0), ("px", 2), ("rfg", 0)]);
+    assert_eq!(terminal, Terminal { accepted: false, workflow: "gd", step: 1 });
+
+    // a pair of parts either side of every threshold, each routed to the workflow that checks it
+    let edge_cases = r"{x=1,m=1,a=1,s=1350}
+{x=1,m=1,a=1,s=1351}
+{x=1,m=1,a=2005,s=1000}
+{x=1,m=1,a=2006,s=1000}
+{x=1,m=2090,a=2006,s=1000}
+{x=1,m=2091,a=2006,s=1000}
+{x=1415,m=1,a=1,s=1}
+{x=1416,m=1,a=1,s=1}
+{x=2662,m=1,a=1,s=1}
+{x=2663,m=1,a=1,s=1}
+{x=1,m=1,a=2006,s=536}
+{x=1,m=1,a=2006,s=537}
+{x=2440,m=1,a=2006,s=1000}
+{x=2441,m=1,a=2006,s=1000}
+{x=1,m=1,a=3333,s=1}
+{x=1,m=1,a=3334,s=1}
+{x=1,m=1,a=1,s=2770}
+{x=1,m=1,a=1,s=2771}
+{x=1,m=1,a=1,s=3448}
+{x=1,m=1,a=1,s=3449}
+{x=1,m=1548,a=1,s=3000}
+{x=1,m=1549,a=1,s=3000}
+{x=1,m=1800,a=1,s=2000}
+{x=1,m=1801,a=1,s=2000}
+{x=1,m=838,a=1,s=2000}
+{x=1,m=839,a=1,s=2000}
+{x=1,m=1,a=1716,s=2000}
+{x=1,m=1,a=1717,s=2000}";
+    let (workflows, _) = input_text.split_once("\n\n").unwrap();
+    let parts = parse_input(&format!("{workflows}\n\n{edge_cases}")).parts;
+    let traces = program.trace_batch(&parts);
+    for ((part, terminal), trace) in parts.iter().zip(program.classify_batch(&parts)).zip(&traces) {
+        assert_eq!(program.is_accepting(terminal), input.process_part(*part));
+        assert_eq!(trace, &program.trace(part));
+        assert_eq!(trace.1, program.terminal(terminal));
+    }
+    // the two parts either side of s<1351 part ways at the very first step
+    assert_eq!(traces[0].0[0], ("in", 0));
+    assert_eq!(traces[1].0[0], ("in", 1));
+
+    let mut rules = input.rules.clone();
+    rules.insert("gd".to_owned(), Rule { name: "gd".to_owned(), steps: vec![RuleStep { condition: None, target_rule: "px".to_owned() }] });
+    assert_eq!(DecisionProgram::compile(&rules).unwrap_err(), CompileError::Cycle("px".to_owned()));
+    rules.insert("gd".to_owned(), Rule { name: "gd".to_owned(), steps: vec![RuleStep { condition: None, target_rule: "zz".to_owned() }] });
+    assert_eq!(DecisionProgram::compile(&rules).unwrap_err(), CompileError::UnknownWorkflow("zz".to_owned()));
+    rules.insert("gd".to_owned(), Rule { name: "gd".to_owned(), steps: vec![rules["pv"].steps[0].clone()] });
+    assert_eq!(DecisionProgram::compile(&rules).unwrap_err(), CompileError::NoFallback("gd".to_owned()));
+}
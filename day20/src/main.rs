@@ -1,5 +1,4 @@
 use std::collections::{HashMap, VecDeque, HashSet, BTreeMap};
-use std::time::Instant;
 use std::hash::Hash;
 use num::integer::lcm;
 
@@ -118,14 +117,6 @@ impl Module {
             },
         }
     }
-
-    fn destinations(&self) -> Vec<String> {
-        match self {
-            Module::FlipFlop(f) => f.destinations.clone(),
-            Module::Conjunction(c) => c.destinations.clone(),
-            Module::Broadcaster(b) => b.destinations.clone(),
-        }
-    }
 }
 
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
@@ -162,19 +153,13 @@ impl ModuleConfiguration {
     }
 
     pub fn run_once(&mut self, start: &str, target: &str) -> Vec<PulseType> {
-        let (mut low_pulses, mut high_pulses) = (0_u64, 0_u64);
         let mut pulses_to_target = Vec::new();
         let input_pulse = Pulse::low(String::from("broadcaster"), String::from(start));
         let mut pulse_queue = VecDeque::new();
         pulse_queue.push_back(input_pulse);
 
         while let Some(pulse) = pulse_queue.pop_front() {
-            match pulse.pulse_type {
-                PulseType::Low => { low_pulses += 1; },
-                PulseType::High => { high_pulses += 1; },
-            }
-
-            if &pulse.destination == target {
+            if pulse.destination == target {
                 pulses_to_target.push(pulse.pulse_type);
             }
 
@@ -202,10 +187,10 @@ pub fn parse_input(input: &str) -> ModuleConfiguration {
     for line in input.lines() {
         let (sender, destinations) = line.split_once(" -> ").unwrap();
         let destinations = destinations.split(',').map(|s| s.trim().to_owned()).collect::<Vec<_>>();
-        if sender.starts_with('%') {
-            flipflops.insert(sender[1..].to_owned(), destinations);
-        } else if sender.starts_with('&') {
-            conjunctions.insert(sender[1..].to_owned(), destinations);
+        if let Some(name) = sender.strip_prefix('%') {
+            flipflops.insert(name.to_owned(), destinations);
+        } else if let Some(name) = sender.strip_prefix('&') {
+            conjunctions.insert(name.to_owned(), destinations);
         } else if sender == "broadcaster" {
             // should only hit this once
             broadcaster_outputs.extend(destinations);
@@ -218,13 +203,13 @@ pub fn parse_input(input: &str) -> ModuleConfiguration {
         let conjunctions_sending_here = conjunctions.iter()
             .filter_map(|(cc, ds)| ds.contains(c).then_some(cc.clone()))
             .collect::<Vec<_>>();
-        conjunction_inputs.entry(c.to_owned()).or_insert(HashSet::new()).extend(conjunctions_sending_here);
+        conjunction_inputs.entry(c.to_owned()).or_default().extend(conjunctions_sending_here);
         let flipflops_sending_here = flipflops.iter()
             .filter_map(|(f, ds)| ds.contains(c).then_some(f.clone()))
             .collect::<Vec<_>>();
-        conjunction_inputs.entry(c.to_owned()).or_insert(HashSet::new()).extend(flipflops_sending_here);
+        conjunction_inputs.entry(c.to_owned()).or_default().extend(flipflops_sending_here);
         if broadcaster_outputs.contains(c) {
-            conjunction_inputs.entry(c.to_owned()).or_insert(HashSet::new()).insert(String::from("broadcaster"));
+            conjunction_inputs.entry(c.to_owned()).or_default().insert(String::from("broadcaster"));
         }
     }
 
@@ -252,18 +237,16 @@ pub struct Input {
 }
 
 pub fn parse_input_2(input: &str) -> Input {
-    let mut modules: BTreeMap<String, Module> = BTreeMap::new();
-
     let mut conjunctions: HashMap<String, Vec<_>> = HashMap::new();
     let mut flipflops = HashMap::new();
     let mut broadcaster_outputs: Vec<String> = Vec::new();
     for line in input.lines() {
         let (sender, destinations) = line.split_once(" -> ").unwrap();
         let destinations = destinations.split(',').map(|s| s.trim().to_owned()).collect::<Vec<_>>();
-        if sender.starts_with('%') {
-            flipflops.insert(sender[1..].to_owned(), destinations);
-        } else if sender.starts_with('&') {
-            conjunctions.insert(sender[1..].to_owned(), destinations);
+        if let Some(name) = sender.strip_prefix('%') {
+            flipflops.insert(name.to_owned(), destinations);
+        } else if let Some(name) = sender.strip_prefix('&') {
+            conjunctions.insert(name.to_owned(), destinations);
         } else if sender == "broadcaster" {
             // should only hit this once
             broadcaster_outputs.extend(destinations);
@@ -276,13 +259,13 @@ pub fn parse_input_2(input: &str) -> Input {
         let conjunctions_sending_here = conjunctions.iter()
             .filter_map(|(cc, ds)| ds.contains(c).then_some(cc.clone()))
             .collect::<Vec<_>>();
-        conjunction_inputs.entry(c.to_owned()).or_insert(HashSet::new()).extend(conjunctions_sending_here);
+        conjunction_inputs.entry(c.to_owned()).or_default().extend(conjunctions_sending_here);
         let flipflops_sending_here = flipflops.iter()
             .filter_map(|(f, ds)| ds.contains(c).then_some(f.clone()))
             .collect::<Vec<_>>();
-        conjunction_inputs.entry(c.to_owned()).or_insert(HashSet::new()).extend(flipflops_sending_here);
+        conjunction_inputs.entry(c.to_owned()).or_default().extend(flipflops_sending_here);
         if broadcaster_outputs.contains(c) {
-            conjunction_inputs.entry(c.to_owned()).or_insert(HashSet::new()).insert(String::from("broadcaster"));
+            conjunction_inputs.entry(c.to_owned()).or_default().insert(String::from("broadcaster"));
         }
     }
 
@@ -345,13 +328,17 @@ pub fn part_1(mut module_config: ModuleConfiguration) -> u64 {
 pub fn part_2(input: &Input) -> u64 {
     let mut cycle_lengths = Vec::new();
 
-    for (module, start) in input.modules.iter().zip(input.start_points.iter()).clone() {
-        let mut seen_states = HashMap::new();
-        let mut module = module.clone();
-        let state = module.clone();
-        seen_states.insert(state, 0);
+    for (module, start) in input.modules.iter().zip(input.start_points.iter()) {
+        let press = |state: &ModuleConfiguration| {
+            let mut state = state.clone();
+            state.run_once(start, &input.destination);
+            state
+        };
+        let cycle = cycles::find_cycle(module, press, ModuleConfiguration::clone);
 
-        for button_press in 1 .. {
+        // go round again up to the point the states start repeating, watching what reaches the target
+        let mut module = module.clone();
+        for button_press in 1 ..= cycle.start() + cycle.length() {
             let pulses_to_target = module.run_once(start, &input.destination);
             if pulses_to_target.contains(&PulseType::High) {
                 println!("Sent a high pulse to target on press {}", button_press);
@@ -360,14 +347,7 @@ pub fn part_2(input: &Input) -> u64 {
                 // that this is how the cycles line up for some reason
                 // and even then, I'm not convinced that we can be _sure_ it's right; what if there's a very close
                 // overlap earlier on that means we luck out and still have a 'high' remembered from elsewhere?
-                cycle_lengths.push(button_press as u64);
-                
-            } 
-
-            let state = module.clone();
-            if let Some(previous_state) = seen_states.insert(state, button_press) {
-                // println!("Saw the same state after pressing the button {} times as after {} times", button_press, previous_state);
-                break;
+                cycle_lengths.push(button_press);
             }
         }
     }
@@ -407,4 +387,19 @@ pub fn test_loop() {
 
     let module_config = parse_input(input);
     assert_eq!(part_1(module_config), 11_687_500);
-}
\ No newline at end of file
+}
+
+#[test]
+pub fn test_part2() {
+    // two counters feeding the conjunction before rx: a and b light up every 2 presses, c, d and e every 4
+    let input = r"broadcaster -> a, c
+%a -> b
+%b -> con
+%c -> d
+%d -> e
+%e -> con
+&con -> rx";
+
+    let input = parse_input_2(input);
+    assert_eq!(part_2(&input), 4);
+}